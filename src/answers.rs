@@ -0,0 +1,81 @@
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    path::Path,
+};
+
+use anyhow::Result;
+
+use crate::blueprint::Blueprint;
+
+pub const FILE_NAME: &str = ".ranger-answers.yaml";
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Answers {
+    #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+    pub source: Source,
+    pub version: String,
+    pub variables: BTreeMap<String, String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Local {
+        path: String,
+    },
+    Git {
+        repo: String,
+        #[serde(rename = "ref", alias = "branch", default, skip_serializing_if = "Option::is_none")]
+        reference: Option<String>,
+        folder: String,
+        commit: String,
+    },
+    Archive {
+        path: String,
+        folder: String,
+    },
+}
+
+impl Source {
+    /// The repository URL or local path of the template.
+    pub fn location(&self) -> &str {
+        match self {
+            | Source::Local { path } => path,
+            | Source::Archive { path, .. } => path,
+            | Source::Git { repo, .. } => repo,
+        }
+    }
+}
+
+impl Answers {
+    /// Records the values of the variables declared by `blueprint`, except for
+    /// the ones with `record: false`. Undeclared values are never recorded.
+    pub fn new(source: Source, blueprint: &Blueprint, values: &HashMap<String, String>) -> Self {
+        Self {
+            source,
+            version: blueprint.version.to_owned(),
+            variables: values
+                .iter()
+                .filter(|(k, _)| {
+                    blueprint.template.variables.as_ref().and_then(|vars| vars.get(*k)).is_some_and(|v| v.record)
+                })
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+        }
+    }
+
+    pub fn load(out_path_root: &Path) -> Result<Self> {
+        let path = Path::join(out_path_root, FILE_NAME);
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("failed to read answers file {}: {}", path.display(), e))?;
+        Ok(serde_yaml::from_str::<Self>(&data)?)
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{
+        Component,
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::Result;
+
+#[derive(Debug)]
+pub enum Format {
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl Format {
+    /// Detects the archive format from the file name.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Self::TarZst)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Extracts `archive` into `target`. Entries that would end up outside of
+/// `target` (absolute paths, `..` components or writes through symlinks) are
+/// rejected.
+pub fn extract(archive: &Path, target: &Path) -> Result<()> {
+    let format =
+        Format::detect(archive).ok_or_else(|| anyhow::anyhow!("unsupported archive format: {}", archive.display()))?;
+    std::fs::create_dir_all(target)?;
+    let file =
+        File::open(archive).map_err(|e| anyhow::anyhow!("failed to open archive {}: {}", archive.display(), e))?;
+    match format {
+        | Format::TarGz => extract_tar(archive, flate2::read::GzDecoder::new(file), target),
+        | Format::TarZst => extract_tar(archive, zstd::Decoder::new(file)?, target),
+        | Format::Zip => extract_zip(archive, file, target),
+    }
+}
+
+fn extract_tar(archive: &Path, reader: impl Read, target: &Path) -> Result<()> {
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        enclosed(archive, &path)?;
+        // `unpack_in` additionally verifies that the parent directory does not
+        // resolve outside of the target through a previously extracted symlink
+        let unpacked = entry.unpack_in(target).map_err(|e| {
+            anyhow::anyhow!(
+                "failed to extract \"{}\" from archive {}: {}",
+                path.display(),
+                archive.display(),
+                e
+            )
+        })?;
+        if !unpacked {
+            return Err(unsafe_entry(archive, &path));
+        }
+    }
+    Ok(())
+}
+
+fn extract_zip(archive: &Path, file: File, target: &Path) -> Result<()> {
+    let mut zip = zip::ZipArchive::new(file)?;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let path = enclosed(archive, Path::new(entry.name()))?;
+        if entry.is_symlink() {
+            return Err(anyhow::anyhow!(
+                "symlinks are not supported in zip archives (entry \"{}\" in {})",
+                entry.name(),
+                archive.display()
+            ));
+        }
+        let out_path = target.join(path);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path)?;
+            continue;
+        }
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut out = File::create(&out_path)?;
+        std::io::copy(&mut entry, &mut out)?;
+    }
+    Ok(())
+}
+
+/// Returns `path` if it is relative and does not leave its root.
+fn enclosed(archive: &Path, path: &Path) -> Result<PathBuf> {
+    let mut enclosed = PathBuf::new();
+    for component in path.components() {
+        match component {
+            | Component::Normal(c) => enclosed.push(c),
+            | Component::CurDir => {},
+            | Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(unsafe_entry(archive, path));
+            },
+        }
+    }
+    Ok(enclosed)
+}
+
+fn unsafe_entry(archive: &Path, path: &Path) -> anyhow::Error {
+    anyhow::anyhow!(
+        "unsafe path \"{}\" in archive {}: it would be extracted outside of the target directory",
+        path.display(),
+        archive.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// Writes a `.tar.gz` with the given entries. The names are written into
+    /// the header verbatim, as `tar::Builder` refuses to create unsafe paths.
+    fn tar_gz(dir: &Path, entries: &[&str]) -> PathBuf {
+        let path = dir.join("template.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        ));
+        for name in entries {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(1);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, &b"x"[..]).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    fn zip(dir: &Path, entries: &[&str]) -> PathBuf {
+        let path = dir.join("template.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        for name in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(b"x").unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn extracts_safe_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("out");
+        extract(&tar_gz(dir.path(), &["a/b.txt", "./c.txt"]), &target).unwrap();
+        assert!(target.join("a/b.txt").is_file());
+        assert!(target.join("c.txt").is_file());
+
+        let target = dir.path().join("out-zip");
+        extract(&zip(dir.path(), &["a/b.txt"]), &target).unwrap();
+        assert!(target.join("a/b.txt").is_file());
+    }
+
+    #[test]
+    fn rejects_tar_entries_leaving_the_target() {
+        for name in ["../evil.txt", "a/../../evil.txt", "/tmp/evil.txt"] {
+            let dir = tempfile::tempdir().unwrap();
+            let target = dir.path().join("out");
+            let err = extract(&tar_gz(dir.path(), &[name]), &target).unwrap_err();
+            assert!(err.to_string().contains("unsafe path"), "{}: {}", name, err);
+            assert!(!dir.path().join("evil.txt").exists());
+        }
+    }
+
+    #[test]
+    fn rejects_zip_slip() {
+        for name in ["../evil.txt", "a/../../evil.txt", "/tmp/evil.txt"] {
+            let dir = tempfile::tempdir().unwrap();
+            let target = dir.path().join("out");
+            let err = extract(&zip(dir.path(), &[name]), &target).unwrap_err();
+            assert!(err.to_string().contains("unsafe path"), "{}: {}", name, err);
+            assert!(!dir.path().join("evil.txt").exists());
+        }
+    }
+}
@@ -1,15 +1,21 @@
 use std::{
     collections::HashMap,
+    ffi::OsString,
+    path::Path,
     str::FromStr,
 };
 
 use anyhow::Result;
 use clap::{
+    parser::ValueSource,
     Arg,
     ArgAction,
 };
 
-use crate::error::Error;
+use crate::{
+    blueprint::Blueprint,
+    error::Error,
+};
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Privilege {
@@ -46,32 +52,174 @@ pub enum ManualFormat {
 
 #[derive(Debug)]
 pub enum Command {
-    Manual { path: String, format: ManualFormat },
-    Autocomplete { path: String, shell: clap_complete::Shell },
+    Manual {
+        path: String,
+        format: ManualFormat,
+    },
+    Autocomplete {
+        path: String,
+        shell: clap_complete::Shell,
+    },
 
     Generate(GenerateCommand),
+    Cache(CacheCommand),
+    Templates(TemplatesCommand),
+    Validate {
+        folder: String,
+    },
+    Inspect {
+        source: InspectSource,
+        output: OutputFormat,
+    },
+    Update {
+        git: GitOptions,
+        options: UpdateOptions,
+    },
+}
+
+/// Variable values given on the command line, from varfiles or the
+/// environment.
+#[derive(Debug, Default, Clone)]
+pub struct Vars {
+    pub values: HashMap<String, String>,
+    /// Where every value came from (`varfile 1 (vars.yaml)`, `env (NAME)`,
+    /// `cli`).
+    pub origins: HashMap<String, String>,
+    /// Prefix of the environment variables that set template variables.
+    pub env_prefix: Option<String>,
+}
+
+impl Vars {
+    fn insert(&mut self, key: String, value: String, origin: String) {
+        self.origins.insert(key.clone(), origin);
+        self.values.insert(key, value);
+    }
+
+    /// Adds the values of the environment variables starting with the env
+    /// prefix. Only variables declared by the template are read, so unrelated
+    /// variables sharing the prefix (like `RANGER_GIT_TOKEN`) are never picked
+    /// up. Values given with `--var` take precedence.
+    pub fn with_env(&self, bp: &Blueprint) -> Self {
+        let mut vars = self.clone();
+        let (Some(prefix), Some(declared)) = (&self.env_prefix, &bp.template.variables) else {
+            return vars;
+        };
+        let mut values = std::env::vars().filter(|(k, _)| k.starts_with(prefix.as_str())).collect::<Vec<_>>();
+        values.sort();
+        for (name, v) in values {
+            let key = name[prefix.len()..].to_lowercase().replace("__", ".");
+            if declared.contains_key(&key) && self.origins.get(&key).is_none_or(|o| o != "cli") {
+                vars.insert(key, v, format!("env ({})", name));
+            }
+        }
+        vars
+    }
+}
+
+#[derive(Debug)]
+pub enum ConflictStrategy {
+    Skip,
+    Overwrite,
+    Prompt,
+    Backup,
+    Fail,
+}
+
+#[derive(Debug)]
+pub enum Trust {
+    None,
+    Prompt,
+    Ultimate,
+}
+
+#[derive(Debug)]
+pub struct GenerateOptions {
+    pub out: String,
+    pub vars: Vars,
+    pub interactive: bool,
+    pub verbose: bool,
+    pub strict: bool,
+    pub force: bool,
+    pub merge: Option<ConflictStrategy>,
+    pub dry_run: bool,
+    pub answers: bool,
+    pub trust: Trust,
+}
+
+#[derive(Debug)]
+pub struct UpdateOptions {
+    pub out: String,
+    pub reference: Option<String>,
+    pub vars: Vars,
+    pub interactive: bool,
+    pub verbose: bool,
+    pub strict: bool,
+    pub dry_run: bool,
+    pub trust: Trust,
+}
+
+#[derive(Debug)]
+pub struct GitOptions {
+    pub ssh_key: Option<String>,
+    pub offline: bool,
+}
+
+#[derive(Debug)]
+pub enum CacheCommand {
+    List,
+    Clean { repo: Option<String> },
 }
 
 #[derive(Debug)]
 pub enum GenerateCommand {
     Local {
-        out: String,
         folder: String,
-        vars: HashMap<String, String>,
-        interactive: bool,
-        force: bool,
+        options: GenerateOptions,
     },
     Git {
-        out: String,
         repo: String,
-        branch: String,
+        reference: Option<String>,
         folder: String,
-        vars: HashMap<String, String>,
-        interactive: bool,
-        force: bool,
+        git: GitOptions,
+        options: GenerateOptions,
+    },
+    Archive {
+        archive: String,
+        folder: String,
+        options: GenerateOptions,
+    },
+    Alias {
+        name: String,
+        reference: Option<String>,
+        git: GitOptions,
+        options: GenerateOptions,
     },
 }
 
+#[derive(Debug)]
+pub enum InspectSource {
+    Local {
+        folder: String,
+    },
+    Git {
+        repo: String,
+        reference: Option<String>,
+        folder: String,
+        git: GitOptions,
+    },
+}
+
+#[derive(Debug)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+pub enum TemplatesCommand {
+    List,
+}
+
 pub struct ClapArgumentLoader {}
 
 impl ClapArgumentLoader {
@@ -114,55 +262,283 @@ impl ClapArgumentLoader {
             .subcommand(
                 clap::Command::new("generate")
                     .subcommand_required(true)
-                    .about("Generate command.")
+                    .allow_external_subcommands(true)
+                    .about("Generate command. `ranger generate <alias>` generates from a template alias.")
                     .subcommand(
                         clap::Command::new("git")
                             .about("Generate from git repo.")
-                            .arg(clap::Arg::new("out").short('o').long("out").required(true))
                             .arg(
                                 clap::Arg::new("repo")
                                     .short('r')
                                     .long("repo")
                                     .default_value("https://github.com/replicadse/ranger.git"),
                             )
-                            .arg(clap::Arg::new("branch").short('b').long("branch").default_value("master"))
+                            .arg(Self::ref_arg(
+                                "The branch, tag or commit to generate from. Defaults to the default branch of the \
+                                 repo.",
+                            ))
                             .arg(clap::Arg::new("folder").short('f').long("folder").default_value("./"))
-                            .arg(
-                                clap::Arg::new("var").short('v').long("var").action(ArgAction::Append).help(
-                                    "A variable in the template (placeholder). This takes precendence over varfile.",
-                                ),
-                            )
-                            .arg(
-                                clap::Arg::new("varfile")
-                                    .long("varfile")
-                                    .help("A file path containing variables in the template (placeholder)."),
-                            )
-                            .arg(
-                                clap::Arg::new("interactive").long("interactive").short('i').action(ArgAction::SetTrue),
-                            )
-                            .arg(clap::Arg::new("force").long("force").action(ArgAction::SetTrue)),
+                            .args(Self::git_args())
+                            .arg(Self::trust_arg("prompt"))
+                            .args(Self::generate_args())
+                            .args(Self::variable_args()),
+                    )
+                    .subcommand(
+                        clap::Command::new("archive")
+                            .about("Generate from a .tar.gz, .tar.zst or .zip archive.")
+                            .arg(clap::Arg::new("archive").short('a').long("archive").required(true))
+                            .arg(clap::Arg::new("folder").short('f').long("folder").default_value("./"))
+                            .arg(Self::trust_arg("prompt"))
+                            .args(Self::generate_args())
+                            .args(Self::variable_args()),
                     )
                     .subcommand(
                         clap::Command::new("local")
-                            .about("Generate from a local source folder.")
-                            .arg(clap::Arg::new("out").short('o').long("out").required(true))
+                            .about("Generate from a local source folder or archive.")
                             .arg(clap::Arg::new("folder").short('f').long("folder").required(true))
-                            .arg(
-                                clap::Arg::new("var").short('v').long("var").action(ArgAction::Append).help(
-                                    "A variable in the template (placeholder). This takes precendence over varfile.",
-                                ),
-                            )
-                            .arg(
-                                clap::Arg::new("varfile")
-                                    .long("varfile")
-                                    .help("A file path containing variables in the template (placeholder)."),
-                            )
-                            .arg(
-                                clap::Arg::new("interactive").long("interactive").short('i').action(ArgAction::SetTrue),
-                            )
-                            .arg(clap::Arg::new("force").long("force").action(ArgAction::SetTrue)),
+                            .arg(Self::trust_arg("ultimate"))
+                            .args(Self::generate_args())
+                            .args(Self::variable_args()),
                     ),
             )
+            .subcommand(
+                clap::Command::new("validate")
+                    .about(
+                        "Check a local template for blueprint, syntax and variable errors without generating it. \
+                         Exits non-zero if errors were found.",
+                    )
+                    .arg(clap::Arg::new("folder").short('f').long("folder").required(true)),
+            )
+            .subcommand(
+                clap::Command::new("inspect")
+                    .subcommand_required(true)
+                    .about("Describe the variables, helpers and files of a template without generating it.")
+                    .subcommand(
+                        clap::Command::new("git")
+                            .about("Inspect a template in a git repo.")
+                            .arg(clap::Arg::new("repo").short('r').long("repo").required(true))
+                            .arg(Self::ref_arg(
+                                "The branch, tag or commit to inspect. Defaults to the default branch of the repo.",
+                            ))
+                            .arg(clap::Arg::new("folder").short('f').long("folder").default_value("./"))
+                            .args(Self::git_args())
+                            .arg(Self::output_arg()),
+                    )
+                    .subcommand(
+                        clap::Command::new("local")
+                            .about("Inspect a local template folder.")
+                            .arg(clap::Arg::new("folder").short('f').long("folder").required(true))
+                            .arg(Self::output_arg()),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("templates")
+                    .subcommand_required(true)
+                    .about("Manage template aliases.")
+                    .subcommand(clap::Command::new("list").about("List the template aliases from the config file.")),
+            )
+            .subcommand(
+                clap::Command::new("cache")
+                    .subcommand_required(true)
+                    .about("Manage the cache of template repositories.")
+                    .subcommand(clap::Command::new("list").about("List the cached repositories."))
+                    .subcommand(
+                        clap::Command::new("clean").about("Remove cached repositories.").arg(
+                            clap::Arg::new("repo")
+                                .short('r')
+                                .long("repo")
+                                .help("Only remove this repository. Removes everything if omitted."),
+                        ),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("update")
+                    .about("Re-apply the latest version of the template to a previously generated folder.")
+                    .arg(clap::Arg::new("out").short('o').long("out").default_value("./"))
+                    .arg(Self::ref_arg(
+                        "The branch, tag or commit to update to. Defaults to the recorded ref.",
+                    ))
+                    .arg(
+                        clap::Arg::new("dry-run")
+                            .long("dry-run")
+                            .action(ArgAction::SetTrue)
+                            .help("Print the changes that would be applied without writing anything."),
+                    )
+                    .args(Self::git_args())
+                    .arg(Self::trust_arg("prompt"))
+                    .args(Self::variable_args()),
+            )
+    }
+
+    fn alias_command(name: &str) -> clap::Command {
+        clap::Command::new("alias")
+            .bin_name(format!("ranger generate {}", name))
+            .about("Generate from a template alias.")
+            .arg(Self::ref_arg(
+                "The branch, tag or commit to generate from. Defaults to the ref of the alias.",
+            ))
+            .args(Self::git_args())
+            .arg(Self::trust_arg("prompt"))
+            .args(Self::generate_args())
+            .args(Self::variable_args())
+    }
+
+    fn output_arg() -> clap::Arg {
+        clap::Arg::new("output").long("output").value_parser(["text", "json"]).default_value("text")
+    }
+
+    fn parse_output(subc: &clap::ArgMatches) -> Result<OutputFormat> {
+        Ok(match subc.get_one::<String>("output").unwrap().as_str() {
+            | "text" => OutputFormat::Text,
+            | "json" => OutputFormat::Json,
+            | _ => return Err(Error::Argument("unknown output format".into()).into()),
+        })
+    }
+
+    fn ref_arg(help: &'static str) -> clap::Arg {
+        clap::Arg::new("ref").long("ref").alias("branch").short_alias('b').help(help)
+    }
+
+    fn git_args() -> Vec<clap::Arg> {
+        vec![
+            clap::Arg::new("ssh-key").long("ssh-key").help(
+                "A private key file used to authenticate against SSH remotes. The ssh-agent is used if no key is \
+                 given. The passphrase is read from RANGER_SSH_PASSPHRASE.",
+            ),
+            clap::Arg::new("offline")
+                .long("offline")
+                .action(ArgAction::SetTrue)
+                .help("Use the cached copy of the repository without fetching."),
+        ]
+    }
+
+    fn parse_git_options(subc: &clap::ArgMatches) -> GitOptions {
+        GitOptions {
+            ssh_key: subc.get_one::<String>("ssh-key").cloned(),
+            offline: subc.get_flag("offline"),
+        }
+    }
+
+    fn trust_arg(default: &'static str) -> clap::Arg {
+        clap::Arg::new("trust").long("trust").value_parser(["none", "prompt", "ultimate"]).default_value(default).help(
+            "Whether the template may execute shell commands (helpers and shell variables). With `prompt`, all \
+             commands are listed and have to be confirmed unless the source is trusted already.",
+        )
+    }
+
+    fn parse_trust(subc: &clap::ArgMatches) -> Result<Trust> {
+        Ok(match subc.get_one::<String>("trust").unwrap().as_str() {
+            | "none" => Trust::None,
+            | "prompt" => Trust::Prompt,
+            | "ultimate" => Trust::Ultimate,
+            | _ => return Err(Error::Argument("unknown trust level".into()).into()),
+        })
+    }
+
+    fn variable_args() -> Vec<clap::Arg> {
+        vec![
+            clap::Arg::new("var").short('v').long("var").action(ArgAction::Append).help(
+                "A variable in the template (placeholder). This takes precendence over varfiles and environment \
+                 variables.",
+            ),
+            clap::Arg::new("varfile").long("varfile").action(ArgAction::Append).help(
+                "A file containing variables in the template (placeholder). YAML, JSON and TOML files are detected by \
+                 their extension, everything else is read as dotenv file. May be repeated, later files take \
+                 precedence.",
+            ),
+            clap::Arg::new("env-prefix").long("env-prefix").help(
+                "Read variables from environment variables starting with this prefix, e.g. `RANGER_VAR_`. The rest of \
+                 the name is lowercased and `__` is replaced by `.` (RANGER_VAR_APP__NAME sets app.name). Only \
+                 variables declared by the template are read. These take precedence over varfiles.",
+            ),
+            clap::Arg::new("interactive").long("interactive").short('i').action(ArgAction::SetTrue),
+            clap::Arg::new("verbose")
+                .long("verbose")
+                .action(ArgAction::SetTrue)
+                .help("Print every variable value and where it came from."),
+            clap::Arg::new("strict")
+                .long("strict")
+                .action(ArgAction::SetTrue)
+                .help("Fail on references to undefined variables even if the template disables strict mode."),
+        ]
+    }
+
+    fn generate_args() -> Vec<clap::Arg> {
+        vec![
+            clap::Arg::new("out").short('o').long("out").required(true),
+            clap::Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("merge")
+                .help("Delete and recreate the output directory if it exists."),
+            clap::Arg::new("merge")
+                .long("merge")
+                .action(ArgAction::SetTrue)
+                .help("Generate into an existing output directory, keeping files that are not part of the template."),
+            clap::Arg::new("conflict")
+                .long("conflict")
+                .value_parser(["skip", "overwrite", "prompt", "backup", "fail"])
+                .default_value("fail")
+                .requires("merge")
+                .help("What to do with existing files that differ from the rendered ones when merging."),
+            clap::Arg::new("dry-run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .help("Print the files that would be generated without writing anything."),
+            clap::Arg::new("no-answers")
+                .long("no-answers")
+                .action(ArgAction::SetTrue)
+                .help("Do not record the template source and variable values in the output directory."),
+        ]
+    }
+
+    fn parse_vars(subc: &clap::ArgMatches) -> Result<Vars> {
+        let mut vars = Vars::default();
+        for (i, v_argfile) in subc.get_many::<String>("varfile").into_iter().flatten().enumerate() {
+            let mut values = crate::varfile::load(Path::new(v_argfile))?.into_iter().collect::<Vec<_>>();
+            values.sort();
+            for (k, v) in values {
+                vars.insert(k, v, format!("varfile {} ({})", i + 1, v_argfile));
+            }
+        }
+        vars.env_prefix = subc.get_one::<String>("env-prefix").cloned();
+        if let Some(v_arg) = subc.get_many::<String>("var") {
+            for vo in v_arg {
+                let (k, v) = vo
+                    .split_once('=')
+                    .ok_or_else(|| Error::Argument(format!("invalid variable \"{}\", expected KEY=VALUE", vo)))?;
+                vars.insert(k.into(), v.into(), "cli".to_owned());
+            }
+        }
+        Ok(vars)
+    }
+
+    fn parse_generate_options(subc: &clap::ArgMatches) -> Result<GenerateOptions> {
+        let merge = if subc.get_flag("merge") {
+            Some(match subc.get_one::<String>("conflict").unwrap().as_str() {
+                | "skip" => ConflictStrategy::Skip,
+                | "overwrite" => ConflictStrategy::Overwrite,
+                | "prompt" => ConflictStrategy::Prompt,
+                | "backup" => ConflictStrategy::Backup,
+                | "fail" => ConflictStrategy::Fail,
+                | _ => return Err(Error::Argument("unknown conflict strategy".into()).into()),
+            })
+        } else {
+            None
+        };
+
+        Ok(GenerateOptions {
+            out: subc.get_one::<String>("out").unwrap().into(),
+            vars: Self::parse_vars(subc)?,
+            interactive: subc.get_flag("interactive"),
+            verbose: subc.get_flag("verbose"),
+            strict: subc.get_flag("strict"),
+            force: subc.get_flag("force"),
+            merge,
+            dry_run: subc.get_flag("dry-run"),
+            answers: !subc.get_flag("no-answers"),
+            trust: Self::parse_trust(subc)?,
+        })
     }
 
     pub fn load() -> Result<CallArgs> {
@@ -190,54 +566,107 @@ impl ClapArgumentLoader {
             }
         } else if let Some(subc) = command.subcommand_matches("generate") {
             if let Some(subc) = subc.subcommand_matches("git") {
-                let mut vars = HashMap::<String, String>::new();
-                if let Some(v_argfile) = subc.get_one::<String>("varfile") {
-                    let file = std::fs::read_to_string(v_argfile)?;
-                    for vo in file.lines() {
-                        let spl = vo.splitn(2, "=").collect::<Vec<_>>();
-                        vars.insert(spl[0].into(), spl[1].into());
-                    }
-                }
-                if let Some(v_arg) = subc.get_many::<String>("var") {
-                    for vo in v_arg {
-                        let spl = vo.splitn(2, "=").collect::<Vec<_>>();
-                        vars.insert(spl[0].into(), spl[1].into());
-                    }
-                }
                 Command::Generate(GenerateCommand::Git {
-                    out: subc.get_one::<String>("out").unwrap().into(),
                     repo: subc.get_one::<String>("repo").unwrap().into(),
-                    branch: subc.get_one::<String>("branch").unwrap().into(),
+                    reference: subc.get_one::<String>("ref").cloned(),
                     folder: subc.get_one::<String>("folder").unwrap().into(),
-                    vars,
-                    interactive: subc.get_flag("interactive"),
-                    force: subc.get_flag("force"),
+                    git: Self::parse_git_options(subc),
+                    options: Self::parse_generate_options(subc)?,
+                })
+            } else if let Some(subc) = subc.subcommand_matches("archive") {
+                Command::Generate(GenerateCommand::Archive {
+                    archive: subc.get_one::<String>("archive").unwrap().into(),
+                    folder: subc.get_one::<String>("folder").unwrap().into(),
+                    options: Self::parse_generate_options(subc)?,
                 })
             } else if let Some(subc) = subc.subcommand_matches("local") {
-                let mut vars = HashMap::<String, String>::new();
-                if let Some(v_argfile) = subc.get_one::<String>("varfile") {
-                    let file = std::fs::read_to_string(v_argfile)?;
-                    for vo in file.lines() {
-                        let spl = vo.splitn(2, "=").collect::<Vec<_>>();
-                        vars.insert(spl[0].into(), spl[1].into());
+                let folder = subc.get_one::<String>("folder").unwrap();
+                let mut options = Self::parse_generate_options(subc)?;
+                if Path::new(folder).is_file() && crate::archive::Format::detect(Path::new(folder)).is_some() {
+                    // archives are usually downloaded, so they do not inherit the
+                    // `ultimate` default of local folders
+                    if subc.value_source("trust") != Some(ValueSource::CommandLine) {
+                        options.trust = Trust::Prompt;
                     }
+                    Command::Generate(GenerateCommand::Archive {
+                        archive: folder.into(),
+                        folder: "./".into(),
+                        options,
+                    })
+                } else {
+                    Command::Generate(GenerateCommand::Local {
+                        folder: folder.into(),
+                        options,
+                    })
                 }
-                if let Some(v_arg) = subc.get_many::<String>("var") {
-                    for vo in v_arg {
-                        let spl = vo.splitn(2, "=").collect::<Vec<_>>();
-                        vars.insert(spl[0].into(), spl[1].into());
-                    }
+            } else if let Some((name, subc)) = subc.subcommand() {
+                let args = subc.get_many::<OsString>("").into_iter().flatten().cloned();
+                let subc =
+                    Self::alias_command(name).get_matches_from(std::iter::once(OsString::from(name)).chain(args));
+                Command::Generate(GenerateCommand::Alias {
+                    name: name.to_owned(),
+                    reference: subc.get_one::<String>("ref").cloned(),
+                    git: Self::parse_git_options(&subc),
+                    options: Self::parse_generate_options(&subc)?,
+                })
+            } else {
+                return Err(Error::UnknownCommand.into());
+            }
+        } else if let Some(subc) = command.subcommand_matches("validate") {
+            Command::Validate {
+                folder: subc.get_one::<String>("folder").unwrap().into(),
+            }
+        } else if let Some(subc) = command.subcommand_matches("inspect") {
+            if let Some(subc) = subc.subcommand_matches("git") {
+                Command::Inspect {
+                    source: InspectSource::Git {
+                        repo: subc.get_one::<String>("repo").unwrap().into(),
+                        reference: subc.get_one::<String>("ref").cloned(),
+                        folder: subc.get_one::<String>("folder").unwrap().into(),
+                        git: Self::parse_git_options(subc),
+                    },
+                    output: Self::parse_output(subc)?,
                 }
-                Command::Generate(GenerateCommand::Local {
-                    out: subc.get_one::<String>("out").unwrap().into(),
-                    folder: subc.get_one::<String>("folder").unwrap().into(),
-                    vars,
-                    interactive: subc.get_flag("interactive"),
-                    force: subc.get_flag("force"),
+            } else if let Some(subc) = subc.subcommand_matches("local") {
+                Command::Inspect {
+                    source: InspectSource::Local {
+                        folder: subc.get_one::<String>("folder").unwrap().into(),
+                    },
+                    output: Self::parse_output(subc)?,
+                }
+            } else {
+                return Err(Error::UnknownCommand.into());
+            }
+        } else if let Some(subc) = command.subcommand_matches("templates") {
+            if subc.subcommand_matches("list").is_some() {
+                Command::Templates(TemplatesCommand::List)
+            } else {
+                return Err(Error::UnknownCommand.into());
+            }
+        } else if let Some(subc) = command.subcommand_matches("cache") {
+            if subc.subcommand_matches("list").is_some() {
+                Command::Cache(CacheCommand::List)
+            } else if let Some(subc) = subc.subcommand_matches("clean") {
+                Command::Cache(CacheCommand::Clean {
+                    repo: subc.get_one::<String>("repo").cloned(),
                 })
             } else {
                 return Err(Error::UnknownCommand.into());
             }
+        } else if let Some(subc) = command.subcommand_matches("update") {
+            Command::Update {
+                git: Self::parse_git_options(subc),
+                options: UpdateOptions {
+                    out: subc.get_one::<String>("out").unwrap().into(),
+                    reference: subc.get_one::<String>("ref").cloned(),
+                    vars: Self::parse_vars(subc)?,
+                    interactive: subc.get_flag("interactive"),
+                    verbose: subc.get_flag("verbose"),
+                    strict: subc.get_flag("strict"),
+                    dry_run: subc.get_flag("dry-run"),
+                    trust: Self::parse_trust(subc)?,
+                },
+            }
         } else {
             return Err(Error::UnknownCommand.into());
         };
@@ -1,4 +1,13 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    path::Path,
+};
+
+use anyhow::Result;
+use indexmap::IndexMap;
+
+pub const FILE_NAME: &str = ".ranger.yaml";
+pub const SUPPORTED_VERSIONS: &[&str] = &["0.2"];
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -8,9 +17,210 @@ pub struct Blueprint {
     pub template: Template,
 }
 
+impl Blueprint {
+    /// Reads and checks the blueprint of the template in `root_dir`.
+    pub fn load(root_dir: &Path) -> Result<Self> {
+        let bp = Self::read(root_dir)?;
+        if let Some((name, nested)) = bp.overlapping_names().first() {
+            return Err(anyhow::anyhow!(
+                "invalid blueprint {}: variable \"{}\" overlaps with \"{}\", a variable can not have nested variables",
+                Path::join(root_dir, FILE_NAME).display(),
+                name,
+                nested
+            ));
+        }
+        Ok(bp)
+    }
+
+    /// Reads the blueprint without checking it.
+    pub fn read(root_dir: &Path) -> Result<Self> {
+        let path = Path::join(root_dir, FILE_NAME);
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("failed to read blueprint {}: {}", path.display(), e))?;
+        serde_yaml::from_str::<Self>(&data).map_err(|e| anyhow::anyhow!("invalid blueprint {}: {}", path.display(), e))
+    }
+
+    /// Pairs of declared or computed variables where the dotted name of one is
+    /// nested under the other (`docker` and `docker.registry`). Both would be
+    /// the same `vars.docker` in the template context.
+    pub fn overlapping_names(&self) -> Vec<(&str, &str)> {
+        let mut names = self
+            .template
+            .variables
+            .iter()
+            .flat_map(|v| v.keys())
+            .chain(self.template.computed.iter().flat_map(|c| c.keys()))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        names.sort();
+        let mut overlapping = Vec::new();
+        for (i, name) in names.iter().enumerate() {
+            for nested in &names[i + 1..] {
+                if nested.strip_prefix(name).is_some_and(|rest| rest.starts_with('.')) {
+                    overlapping.push((*name, *nested));
+                }
+            }
+        }
+        overlapping
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct Template {
-    pub variables: Option<HashMap<String, complate::config::VariableDefinition>>,
+    pub variables: Option<IndexMap<String, Variable>>,
     pub helpers: std::option::Option<HashMap<String, String>>,
+    pub ignore: Option<Vec<String>>,
+    #[serde(alias = "raw")]
+    pub copy: Option<Vec<String>>,
+    pub rules: Option<HashMap<String, String>>,
+    pub computed: Option<HashMap<String, String>>,
+    pub strict: Option<bool>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Variable {
+    #[serde(flatten)]
+    pub definition: complate::config::VariableDefinition,
+    #[serde(default = "default_record")]
+    pub record: bool,
+    #[serde(rename = "type", default)]
+    pub kind: VariableType,
+    pub values: Option<Vec<String>>,
+    pub validate: Option<Vec<Validation>>,
+    pub when: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableType {
+    Bool,
+    Int,
+    #[default]
+    String,
+    #[serde(rename = "list<string>")]
+    List,
+    Enum,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Validation {
+    Regex(String),
+    MinLength(usize),
+    MaxLength(usize),
+    Min(i64),
+    Max(i64),
+    OneOf(Vec<String>),
+}
+
+impl Validation {
+    fn check(&self, value: &serde_json::Value) -> Result<()> {
+        let ok = match (self, value) {
+            | (_, serde_json::Value::Array(items))
+                if !matches!(self, Validation::MinLength(_) | Validation::MaxLength(_)) =>
+            {
+                return items.iter().try_for_each(|item| self.check(item));
+            },
+            | (Validation::Regex(regex), serde_json::Value::String(v)) => regex::Regex::new(regex)?.is_match(v),
+            | (Validation::MinLength(min), serde_json::Value::String(v)) => v.chars().count() >= *min,
+            | (Validation::MaxLength(max), serde_json::Value::String(v)) => v.chars().count() <= *max,
+            | (Validation::MinLength(min), serde_json::Value::Array(v)) => v.len() >= *min,
+            | (Validation::MaxLength(max), serde_json::Value::Array(v)) => v.len() <= *max,
+            | (Validation::Min(min), serde_json::Value::Number(v)) => v.as_i64().is_some_and(|v| v >= *min),
+            | (Validation::Max(max), serde_json::Value::Number(v)) => v.as_i64().is_some_and(|v| v <= *max),
+            | (Validation::OneOf(options), serde_json::Value::String(v)) => options.contains(v),
+            | _ => return Err(anyhow::anyhow!("{} can not be applied to {}", self, value)),
+        };
+        if !ok {
+            return Err(anyhow::anyhow!("{} failed for {}", self, value));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Validation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Validation::Regex(v) => write!(f, "regex \"{}\"", v),
+            | Validation::MinLength(v) => write!(f, "min_length {}", v),
+            | Validation::MaxLength(v) => write!(f, "max_length {}", v),
+            | Validation::Min(v) => write!(f, "min {}", v),
+            | Validation::Max(v) => write!(f, "max {}", v),
+            | Validation::OneOf(v) => write!(f, "one_of [{}]", v.join(", ")),
+        }
+    }
+}
+
+impl Variable {
+    /// Parses the raw value and checks it against all validation rules.
+    pub fn validate(&self, raw: &str) -> Result<serde_json::Value> {
+        let value = self.parse(raw)?;
+        let failed = self
+            .validate
+            .iter()
+            .flatten()
+            .filter_map(|v| v.check(&value).err())
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        if !failed.is_empty() {
+            return Err(anyhow::anyhow!(failed.join(", ")));
+        }
+        Ok(value)
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        matches!(
+            self.definition,
+            complate::config::VariableDefinition::Prompt(_)
+                | complate::config::VariableDefinition::Select { .. }
+                | complate::config::VariableDefinition::Check { .. }
+        )
+    }
+
+    /// Parses the raw (string) value of the variable into its typed JSON
+    /// representation.
+    pub fn parse(&self, raw: &str) -> Result<serde_json::Value> {
+        Ok(match self.kind {
+            | VariableType::Bool => {
+                match raw.trim().to_lowercase().as_str() {
+                    | "true" | "yes" | "y" | "1" => serde_json::Value::Bool(true),
+                    | "false" | "no" | "n" | "0" => serde_json::Value::Bool(false),
+                    | _ => return Err(anyhow::anyhow!("expected bool, got \"{}\"", raw)),
+                }
+            },
+            | VariableType::Int => {
+                serde_json::Value::from(
+                    raw.trim().parse::<i64>().map_err(|_| anyhow::anyhow!("expected int, got \"{}\"", raw))?,
+                )
+            },
+            | VariableType::String => serde_json::Value::String(raw.to_owned()),
+            | VariableType::List => {
+                let items = if raw.trim_start().starts_with('[') {
+                    serde_json::from_str::<Vec<String>>(raw)
+                        .map_err(|e| anyhow::anyhow!("expected list<string>, got \"{}\": {}", raw, e))?
+                } else {
+                    raw.split(',').map(|v| v.trim().to_owned()).filter(|v| !v.is_empty()).collect()
+                };
+                serde_json::Value::from(items)
+            },
+            | VariableType::Enum => {
+                let values = self.values.as_deref().unwrap_or_default();
+                if !values.iter().any(|v| v == raw) {
+                    return Err(anyhow::anyhow!(
+                        "expected one of [{}], got \"{}\"",
+                        values.join(", "),
+                        raw
+                    ));
+                }
+                serde_json::Value::String(raw.to_owned())
+            },
+        })
+    }
+}
+
+fn default_record() -> bool {
+    true
 }
@@ -0,0 +1,87 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use anyhow::Result;
+use sha2::Digest;
+
+/// The cache of template repositories at `$XDG_CACHE_HOME/ranger`
+/// (defaulting to `~/.cache/ranger`). Every repository is kept as a bare
+/// clone in a folder named after the hash of its URL.
+pub fn dir() -> Result<PathBuf> {
+    let dir = match std::env::var_os("XDG_CACHE_HOME").filter(|v| !v.is_empty()) {
+        | Some(dir) => PathBuf::from(dir),
+        | None => {
+            PathBuf::from(std::env::var_os("HOME").ok_or_else(|| anyhow::anyhow!("can not determine cache directory"))?)
+                .join(".cache")
+        },
+    };
+    Ok(dir.join("ranger").join("repos"))
+}
+
+fn repo_dir(cache_dir: &Path, repo: &str) -> PathBuf {
+    cache_dir.join(hex::encode(sha2::Sha256::digest(repo.as_bytes())))
+}
+
+/// Opens the clone of `repo` in the cache at `cache_dir`, creating an empty
+/// one if there is none. Returns whether it was created.
+pub fn open(cache_dir: &Path, repo: &str) -> Result<(git2::Repository, bool)> {
+    let path = repo_dir(cache_dir, repo);
+    if path.exists() {
+        return Ok((git2::Repository::open_bare(&path)?, false));
+    }
+    std::fs::create_dir_all(&path)?;
+    let repository = git2::Repository::init_bare(&path)?;
+    repository.remote("origin", repo)?;
+    Ok((repository, true))
+}
+
+/// Lists the URL, path and size in bytes of every cached repository.
+pub fn list() -> Result<Vec<(String, PathBuf, u64)>> {
+    let dir = dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut repos = Vec::new();
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let Ok(repository) = git2::Repository::open_bare(&path) else {
+            continue;
+        };
+        let url = repository.find_remote("origin")?.url().unwrap_or_default().to_owned();
+        let size = walkdir::WalkDir::new(&path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.metadata().ok())
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+            .sum();
+        repos.push((url, path, size));
+    }
+    repos.sort();
+    Ok(repos)
+}
+
+/// Removes the cached clone of `repo`, or the whole cache if no repository is
+/// given. Returns the number of removed repositories.
+pub fn clean(repo: Option<&str>) -> Result<usize> {
+    match repo {
+        | Some(repo) => {
+            let path = repo_dir(&dir()?, repo);
+            if !path.exists() {
+                return Ok(0);
+            }
+            std::fs::remove_dir_all(path)?;
+            Ok(1)
+        },
+        | None => {
+            let count = list()?.len();
+            let dir = dir()?;
+            if dir.exists() {
+                std::fs::remove_dir_all(dir)?;
+            }
+            Ok(count)
+        },
+    }
+}
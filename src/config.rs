@@ -0,0 +1,67 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+};
+
+use anyhow::Result;
+
+/// The user configuration stored at `$XDG_CONFIG_HOME/ranger/config.yaml`
+/// (defaulting to `~/.config/ranger/config.yaml`).
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Config {
+    /// Template sources (repository URLs or local paths) that are trusted to
+    /// execute shell commands.
+    #[serde(default)]
+    pub trusted: Vec<String>,
+    /// Named git templates usable as `ranger generate <alias>`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, Alias>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct Alias {
+    pub repo: String,
+    #[serde(rename = "ref", default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    #[serde(default = "default_folder")]
+    pub folder: String,
+}
+
+fn default_folder() -> String {
+    "./".to_owned()
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf> {
+        let dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            | Some(dir) => PathBuf::from(dir),
+            | None => {
+                PathBuf::from(
+                    std::env::var_os("HOME").ok_or_else(|| anyhow::anyhow!("can not determine config directory"))?,
+                )
+                .join(".config")
+            },
+        };
+        Ok(dir.join("ranger").join("config.yaml"))
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = std::fs::read_to_string(&path)?;
+        serde_yaml::from_str(&data).map_err(|e| anyhow::anyhow!("invalid config {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+}
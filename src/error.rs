@@ -7,4 +7,10 @@ pub enum Error {
     Argument(String),
     #[error("unknown command")]
     UnknownCommand,
+    #[error("unsafe path \"{path}\" (template entry \"{entry}\"): {reason}")]
+    UnsafePath {
+        entry: String,
+        path: String,
+        reason: String,
+    },
 }
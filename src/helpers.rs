@@ -0,0 +1,164 @@
+use std::fmt::Write;
+
+use base64::Engine;
+use handlebars::{
+    Context,
+    Handlebars,
+    Helper,
+    HelperDef,
+    RenderContext,
+    RenderError,
+    RenderErrorReason,
+    ScopedJson,
+};
+use heck::{
+    ToKebabCase,
+    ToLowerCamelCase,
+    ToShoutySnakeCase,
+    ToSnakeCase,
+    ToTitleCase,
+    ToUpperCamelCase,
+};
+use serde_json::Value;
+use sha2::Digest;
+
+type HelperFn = fn(&[Value]) -> Result<Value, String>;
+
+/// A helper implemented in rust that maps its parameters to a single value.
+/// Native helpers can be used both directly and as subexpressions.
+struct Native {
+    name: &'static str,
+    func: HelperFn,
+}
+
+impl HelperDef for Native {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let params = h.params().iter().map(|p| p.value().clone()).collect::<Vec<_>>();
+        (self.func)(&params)
+            .map(ScopedJson::Derived)
+            .map_err(|e| RenderErrorReason::Other(format!("{}: {}", self.name, e)).into())
+    }
+}
+
+pub const NATIVE: &[(&str, HelperFn)] = &[
+    ("snake_case", |p| Ok(str_param(p, 0)?.to_snake_case().into())),
+    ("kebab_case", |p| Ok(str_param(p, 0)?.to_kebab_case().into())),
+    ("camel_case", |p| Ok(str_param(p, 0)?.to_lower_camel_case().into())),
+    ("pascal_case", |p| Ok(str_param(p, 0)?.to_upper_camel_case().into())),
+    ("screaming_case", |p| Ok(str_param(p, 0)?.to_shouty_snake_case().into())),
+    ("title_case", |p| Ok(str_param(p, 0)?.to_title_case().into())),
+    ("upper_case", |p| Ok(str_param(p, 0)?.to_uppercase().into())),
+    ("lower_case", |p| Ok(str_param(p, 0)?.to_lowercase().into())),
+    ("pluralize", |p| Ok(pluralize(str_param(p, 0)?).into())),
+    ("trim", |p| Ok(str_param(p, 0)?.trim().into())),
+    ("replace", |p| {
+        Ok(str_param(p, 0)?.replace(str_param(p, 1)?, str_param(p, 2)?).into())
+    }),
+    ("regex_replace", |p| {
+        let regex = regex::Regex::new(str_param(p, 1)?).map_err(|e| e.to_string())?;
+        Ok(regex.replace_all(str_param(p, 0)?, str_param(p, 2)?).into_owned().into())
+    }),
+    ("base64_encode", |p| {
+        Ok(base64::engine::general_purpose::STANDARD.encode(str_param(p, 0)?).into())
+    }),
+    ("base64_decode", |p| {
+        let data = base64::engine::general_purpose::STANDARD.decode(str_param(p, 0)?).map_err(|e| e.to_string())?;
+        Ok(String::from_utf8(data).map_err(|e| e.to_string())?.into())
+    }),
+    ("hex_encode", |p| Ok(hex::encode(str_param(p, 0)?).into())),
+    ("hex_decode", |p| {
+        let data = hex::decode(str_param(p, 0)?).map_err(|e| e.to_string())?;
+        Ok(String::from_utf8(data).map_err(|e| e.to_string())?.into())
+    }),
+    ("url_encode", |p| {
+        Ok(urlencoding::encode(str_param(p, 0)?).into_owned().into())
+    }),
+    ("url_decode", |p| {
+        Ok(urlencoding::decode(str_param(p, 0)?).map_err(|e| e.to_string())?.into_owned().into())
+    }),
+    ("sha256", |p| {
+        Ok(hex::encode(sha2::Sha256::digest(str_param(p, 0)?)).into())
+    }),
+    ("uuid", |_| Ok(uuid::Uuid::new_v4().to_string().into())),
+    ("now", |p| {
+        let now = chrono::Local::now();
+        Ok(match p.first() {
+            | Some(_) => format_date(&now, str_param(p, 0)?)?,
+            | None => now.to_rfc3339(),
+        }
+        .into())
+    }),
+    ("format_date", |p| {
+        let date = chrono::DateTime::parse_from_rfc3339(str_param(p, 0)?).map_err(|e| e.to_string())?;
+        Ok(format_date(&date, str_param(p, 1)?)?.into())
+    }),
+    ("to_json", |p| {
+        Ok(serde_json::to_string(param(p, 0)?).map_err(|e| e.to_string())?.into())
+    }),
+    ("to_yaml", |p| {
+        Ok(serde_yaml::to_string(param(p, 0)?).map_err(|e| e.to_string())?.into())
+    }),
+    ("to_toml", |p| {
+        Ok(toml::to_string(param(p, 0)?).map_err(|e| e.to_string())?.into())
+    }),
+];
+
+pub fn register(hb: &mut Handlebars) {
+    for (name, func) in NATIVE {
+        hb.register_helper(name, Box::new(Native { name, func: *func }));
+    }
+}
+
+fn param(params: &[Value], idx: usize) -> Result<&Value, String> {
+    params.get(idx).ok_or_else(|| format!("missing parameter {}", idx))
+}
+
+fn str_param(params: &[Value], idx: usize) -> Result<&str, String> {
+    param(params, idx)?.as_str().ok_or_else(|| format!("parameter {} is not a string", idx))
+}
+
+/// Formats `date` with a strftime `format`. Invalid formats are reported as
+/// an error instead of panicking like `DelayedFormat::to_string` does.
+fn format_date<Tz>(date: &chrono::DateTime<Tz>, format: &str) -> Result<String, String>
+where
+    Tz: chrono::TimeZone,
+    Tz::Offset: std::fmt::Display,
+{
+    let mut formatted = String::new();
+    write!(formatted, "{}", date.format(format)).map_err(|_| format!("invalid date format \"{}\"", format))?;
+    Ok(formatted)
+}
+
+fn pluralize(word: &str) -> String {
+    let lower = word.to_lowercase();
+    if ["s", "x", "z", "ch", "sh"].iter().any(|s| lower.ends_with(s)) {
+        format!("{}es", word)
+    } else if lower.ends_with('y') && !["ay", "ey", "iy", "oy", "uy"].iter().any(|s| lower.ends_with(s)) {
+        format!("{}ies", &word[..word.len() - 1])
+    } else {
+        format!("{}s", word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_date_format_is_an_error() {
+        let date = chrono::DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z").unwrap();
+        assert_eq!(format_date(&date, "%Y-%m-%d").unwrap(), "2024-01-02");
+        assert!(format_date(&date, "%Q").is_err());
+
+        let mut hb = Handlebars::new();
+        register(&mut hb);
+        let err = hb.render_template("{{ now \"%Q\" }}", &()).unwrap_err();
+        assert!(err.to_string().contains("invalid date format"), "{}", err);
+    }
+}
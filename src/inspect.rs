@@ -0,0 +1,213 @@
+use std::path::Path;
+
+use anyhow::Result;
+use complate::config::{
+    OptionValue,
+    VariableDefinition,
+};
+
+use crate::{
+    args::OutputFormat,
+    blueprint::{
+        Blueprint,
+        VariableType,
+    },
+    helpers,
+    render,
+};
+
+#[derive(Debug, serde::Serialize)]
+struct Inspection<'a> {
+    version: &'a str,
+    variables: Vec<VariableInfo<'a>>,
+    helpers: Vec<HelperInfo<'a>>,
+    files: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct VariableInfo<'a> {
+    name: &'a str,
+    #[serde(rename = "type")]
+    kind: &'a VariableType,
+    /// How the value is obtained (`arg`, `env`, `static`, `prompt`, `shell`,
+    /// `select` or `check`).
+    source: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    env: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    options: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    when: Option<&'a str>,
+    /// Whether resolving the value executes a shell command.
+    shell: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct HelperInfo<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<&'a str>,
+    /// Whether the helper executes a shell command.
+    shell: bool,
+}
+
+/// Prints the version, variables, helpers and file tree of a template.
+pub fn inspect(bp: &Blueprint, root_dir: &Path, output: &OutputFormat) -> Result<()> {
+    let variables = bp
+        .template
+        .variables
+        .iter()
+        .flatten()
+        .map(|(name, variable)| {
+            let mut info = VariableInfo {
+                name,
+                kind: &variable.kind,
+                source: "",
+                prompt: None,
+                default: None,
+                env: None,
+                command: None,
+                description: variable.description.as_deref(),
+                options: Vec::new(),
+                when: variable.when.as_deref(),
+                shell: false,
+            };
+            match &variable.definition {
+                | VariableDefinition::Arg => info.source = "arg",
+                | VariableDefinition::Env(env) => {
+                    info.source = "env";
+                    info.env = Some(env);
+                },
+                | VariableDefinition::Static(value) => {
+                    info.source = "static";
+                    info.default = Some(value);
+                },
+                | VariableDefinition::Prompt(text) => {
+                    info.source = "prompt";
+                    info.prompt = Some(text);
+                },
+                | VariableDefinition::Shell(cmd) => {
+                    info.source = "shell";
+                    info.command = Some(cmd);
+                    info.shell = true;
+                },
+                | VariableDefinition::Select { text, options } | VariableDefinition::Check { text, options, .. } => {
+                    info.source = if matches!(variable.definition, VariableDefinition::Select { .. }) {
+                        "select"
+                    } else {
+                        "check"
+                    };
+                    info.prompt = Some(text);
+                    info.options = options.values().map(|o| o.display.as_str()).collect();
+                    info.shell = options.values().any(|o| matches!(o.value, OptionValue::Shell(_)));
+                },
+            }
+            info
+        })
+        .collect();
+
+    // declared helpers are run with `sh -c`, the built-in ones are native
+    let mut helpers = bp
+        .template
+        .helpers
+        .iter()
+        .flatten()
+        .map(|(name, command)| {
+            HelperInfo {
+                name,
+                command: Some(command),
+                shell: true,
+            }
+        })
+        .collect::<Vec<_>>();
+    helpers.sort_by_key(|h| h.name);
+    helpers.extend(helpers::NATIVE.iter().map(|(name, _)| {
+        HelperInfo {
+            name,
+            command: None,
+            shell: false,
+        }
+    }));
+
+    let mut files = Vec::new();
+    for entry in render::walk_template(root_dir, bp)? {
+        let entry = entry?;
+        let template_path = entry.path().strip_prefix(root_dir)?;
+        let mut path = template_path.display().to_string();
+        if entry.file_type().is_dir() {
+            path.push('/');
+        }
+        files.push(path);
+    }
+
+    let inspection = Inspection {
+        version: &bp.version,
+        variables,
+        helpers,
+        files,
+    };
+    match output {
+        | OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&inspection)?),
+        | OutputFormat::Text => print_text(&inspection)?,
+    }
+    Ok(())
+}
+
+fn print_text(inspection: &Inspection) -> Result<()> {
+    println!("version: {}", inspection.version);
+
+    println!("\nvariables:");
+    for variable in &inspection.variables {
+        let kind = serde_json::to_value(variable.kind)?;
+        let shell = if variable.shell { ", shell" } else { "" };
+        println!(
+            "  {} ({}, {}{})",
+            variable.name,
+            kind.as_str().unwrap_or_default(),
+            variable.source,
+            shell
+        );
+        let details = [
+            ("description", variable.description),
+            ("prompt", variable.prompt),
+            ("default", variable.default),
+            ("env", variable.env),
+            ("command", variable.command),
+            ("when", variable.when),
+        ];
+        for (label, value) in details {
+            if let Some(value) = value {
+                println!("    {}: {}", label, value);
+            }
+        }
+        if !variable.options.is_empty() {
+            println!("    options: {}", variable.options.join(", "));
+        }
+    }
+
+    println!("\nhelpers:");
+    for helper in &inspection.helpers {
+        match helper.command {
+            | Some(command) => println!("  {} (shell): {}", helper.name, command),
+            | None => println!("  {} (built-in)", helper.name),
+        }
+    }
+
+    println!("\nfiles:");
+    for file in &inspection.files {
+        let path = Path::new(file.trim_end_matches('/'));
+        let depth = path.components().count();
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        let suffix = if file.ends_with('/') { "/" } else { "" };
+        println!("  {}{}{}", "  ".repeat(depth - 1), name, suffix);
+    }
+    Ok(())
+}
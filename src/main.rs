@@ -1,22 +1,34 @@
 include!("check_features.rs");
 
+mod answers;
+mod archive;
 pub mod args;
 mod blueprint;
+mod cache;
+mod config;
 pub mod error;
+mod helpers;
+mod inspect;
 pub mod reference;
+mod render;
+mod source;
+mod trust;
+mod update;
+mod validate;
+mod varfile;
 
-use std::{
-    collections::HashMap,
-    path::{
-        Path,
-        PathBuf,
-    },
+use std::path::{
+    Path,
+    PathBuf,
 };
 
 use anyhow::Result;
-use args::ManualFormat;
+use args::{
+    GenerateOptions,
+    GitOptions,
+    ManualFormat,
+};
 use blueprint::Blueprint;
-use git2::FetchOptions;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -45,140 +57,220 @@ async fn main() -> Result<()> {
         | crate::args::Command::Generate(c) => {
             match c {
                 | crate::args::GenerateCommand::Git {
-                    out,
                     repo,
-                    branch,
+                    reference,
                     folder,
-                    vars,
-                    interactive,
-                    force,
+                    git,
+                    options,
+                } => generate_git(repo, reference, folder, &git, &options).await,
+                | crate::args::GenerateCommand::Alias {
+                    name,
+                    reference,
+                    git,
+                    options,
                 } => {
-                    let out_path_root = Path::new(&out);
-                    let temp_dir = Path::join(&std::env::temp_dir(), uuid::Uuid::new_v4().to_string());
-                    let root_dir = Path::join(&temp_dir, &folder);
-
-                    let mut fo = FetchOptions::new();
-                    fo.depth(1);
-
-                    git2::build::RepoBuilder::new().branch(&branch).fetch_options(fo).clone(&repo, &temp_dir)?;
-
-                    if force {
-                        let _ = std::fs::remove_dir_all(&out);
-                    }
-                    if std::fs::create_dir_all(&out).is_err() {
-                        return Err(anyhow::anyhow!(
-                            "failed to create output directory - might already exist"
-                        ));
-                    }
-
-                    let blueprint = serde_yaml::from_str::<Blueprint>(
-                        &std::fs::read_to_string(Path::join(&temp_dir, &folder).join(".ranger.yaml")).unwrap(),
+                    let config = config::Config::load()?;
+                    let alias = config.templates.get(&name).ok_or_else(|| {
+                        anyhow::anyhow!("unknown template alias \"{}\" - see `ranger templates list`", name)
+                    })?;
+                    let reference = reference.or(alias.reference.to_owned());
+                    generate_git(
+                        alias.repo.to_owned(),
+                        reference,
+                        alias.folder.to_owned(),
+                        &git,
+                        &options,
                     )
-                    .unwrap();
-                    let render_result = render(&blueprint, &vars, &root_dir, out_path_root, interactive).await;
-                    std::fs::remove_dir_all(temp_dir)?; // remove temp dir in any case
-
-                    match render_result {
-                        | Ok(_) => Ok(()),
-                        | Err(e) => {
-                            std::fs::remove_dir_all(out_path_root)?;
-                            Err(e)
-                        },
-                    }
+                    .await
                 },
-                | crate::args::GenerateCommand::Local {
-                    out,
+                | crate::args::GenerateCommand::Archive {
+                    archive,
                     folder,
-                    vars,
-                    interactive,
-                    force,
-                } => {
-                    let out_path_root = Path::new(&out);
+                    options,
+                } => generate_archive(Path::new(&archive), &folder, &options).await,
+                | crate::args::GenerateCommand::Local { folder, options } => {
                     let folder = Path::new(&folder);
-
-                    let mut fo = FetchOptions::new();
-                    fo.depth(1);
-
-                    if force {
-                        let _ = std::fs::remove_dir_all(&out);
-                    }
-                    if std::fs::create_dir_all(&out).is_err() {
-                        return Err(anyhow::anyhow!(
-                            "failed to create output directory - might already exist"
-                        ));
+                    let blueprint = Blueprint::load(folder)?;
+                    let source = answers::Source::Local {
+                        path: std::fs::canonicalize(folder)?.to_string_lossy().to_string(),
+                    };
+                    generate(&blueprint, folder, source, &options).await
+                },
+            }
+        },
+        | crate::args::Command::Validate { folder } => validate::validate(Path::new(&folder)),
+        | crate::args::Command::Inspect { source, output } => {
+            match source {
+                | crate::args::InspectSource::Local { folder } => {
+                    let folder = Path::new(&folder);
+                    inspect::inspect(&Blueprint::load(folder)?, folder, &output)
+                },
+                | crate::args::InspectSource::Git {
+                    repo,
+                    reference,
+                    folder,
+                    git,
+                } => {
+                    let temp_dir = Path::join(&std::env::temp_dir(), uuid::Uuid::new_v4().to_string());
+                    let root_dir = Path::join(&temp_dir, &folder);
+                    let result = source::clone_git(&repo, reference.as_deref(), &git, &temp_dir)
+                        .and_then(|_| inspect::inspect(&Blueprint::load(&root_dir)?, &root_dir, &output));
+                    let _ = std::fs::remove_dir_all(temp_dir); // remove temp dir in any case
+                    result
+                },
+            }
+        },
+        | crate::args::Command::Templates(c) => {
+            match c {
+                | crate::args::TemplatesCommand::List => {
+                    let templates = config::Config::load()?.templates;
+                    let width = templates.keys().map(|k| k.len()).max().unwrap_or_default();
+                    for (name, alias) in templates {
+                        println!(
+                            "{:<width$}  {}  ref={}  folder={}",
+                            name,
+                            alias.repo,
+                            alias.reference.as_deref().unwrap_or("(default branch)"),
+                            alias.folder,
+                            width = width
+                        );
                     }
-
-                    let blueprint = serde_yaml::from_str::<Blueprint>(
-                        &std::fs::read_to_string(Path::join(folder, ".ranger.yaml")).unwrap(),
-                    )
-                    .unwrap();
-
-                    match render(&blueprint, &vars, &folder, out_path_root, interactive).await {
-                        | Ok(_) => Ok(()),
-                        | Err(e) => {
-                            std::fs::remove_dir_all(out_path_root)?;
-                            Err(e)
-                        },
+                },
+            }
+            Ok(())
+        },
+        | crate::args::Command::Cache(c) => {
+            match c {
+                | crate::args::CacheCommand::List => {
+                    for (url, path, size) in cache::list()? {
+                        println!("{:>10}  {}  {}", size, url, path.display());
                     }
                 },
+                | crate::args::CacheCommand::Clean { repo } => {
+                    let removed = cache::clean(repo.as_deref())?;
+                    println!("removed {} cached repositories", removed);
+                },
             }
+            Ok(())
+        },
+        | crate::args::Command::Update { git, options } => {
+            let backend = if options.interactive {
+                &complate::render::Backend::CLI
+            } else {
+                &complate::render::Backend::Headless
+            };
+            update::update(&options, &git, backend).await
         },
     }
 }
 
-async fn render(
-    bp: &Blueprint,
-    value_overrides: &HashMap<String, String>,
+async fn generate_git(
+    repo: String,
+    reference: Option<String>,
+    folder: String,
+    git: &GitOptions,
+    options: &GenerateOptions,
+) -> Result<()> {
+    let temp_dir = Path::join(&std::env::temp_dir(), uuid::Uuid::new_v4().to_string());
+    let root_dir = Path::join(&temp_dir, &folder);
+
+    let result = async {
+        let commit = source::clone_git(&repo, reference.as_deref(), git, &temp_dir)?;
+        let source = answers::Source::Git {
+            repo,
+            reference,
+            folder,
+            commit,
+        };
+        let blueprint = Blueprint::load(&root_dir)?;
+        generate(&blueprint, &root_dir, source, options).await
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(temp_dir); // remove temp dir in any case
+    result
+}
+
+async fn generate_archive(archive: &Path, folder: &str, options: &GenerateOptions) -> Result<()> {
+    let temp_dir = Path::join(&std::env::temp_dir(), uuid::Uuid::new_v4().to_string());
+    let root_dir = Path::join(&temp_dir, folder);
+
+    let result = async {
+        archive::extract(archive, &temp_dir)?;
+        let source = answers::Source::Archive {
+            path: std::fs::canonicalize(archive)?.to_string_lossy().to_string(),
+            folder: folder.to_owned(),
+        };
+        let blueprint = Blueprint::load(&root_dir)?;
+        generate(&blueprint, &root_dir, source, options).await
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(temp_dir); // remove temp dir in any case
+    result
+}
+
+async fn generate(
+    blueprint: &Blueprint,
     root_dir: &Path,
-    out_path_root: &Path,
-    interactive: bool,
-) -> Result<(), anyhow::Error> {
-    let backend = if interactive {
+    source: answers::Source,
+    options: &GenerateOptions,
+) -> Result<()> {
+    let out_path_root = Path::new(&options.out);
+    let backend = if options.interactive {
         &complate::render::Backend::CLI
     } else {
         &complate::render::Backend::Headless
     };
-    let values = if let Some(variables) = &bp.template.variables {
-        complate::render::populate_variables(
-            variables,
-            value_overrides,
-            &complate::render::ShellTrust::Ultimate,
-            backend,
-            Some("vars".to_owned()),
-        )
-        .await?
-    } else {
-        HashMap::<_, _>::new()
-    };
-
-    let hb = complate::render::make_handlebars(
-        &values,
-        &bp.template.helpers,
-        &complate::render::ShellTrust::Ultimate,
-        true,
-    )
-    .await?;
 
-    for w in walkdir::WalkDir::new(root_dir) {
-        let entry = w?;
-        let path = entry.path();
+    let vars = options.vars.with_env(blueprint);
+    let shell_trust = trust::resolve(&options.trust, source.location(), &[blueprint], backend).await?;
+    let mut plan = render::render(blueprint, &vars.values, root_dir, backend, &shell_trust, options.strict).await?;
+    if options.verbose {
+        plan.print_values(&vars.origins);
+    }
+    if options.answers {
+        let answers = answers::Answers::new(source, blueprint, &plan.values);
+        plan.entries.push(render::Entry {
+            path: PathBuf::from(answers::FILE_NAME),
+            content: render::Content::File(answers.to_yaml()?.into_bytes()),
+        });
+    }
+    // a dry run may preview the changes to an existing folder
+    let exists = out_path_root.exists() && !options.force && options.merge.is_none();
+    if exists && !options.dry_run {
+        return Err(anyhow::anyhow!(
+            "output directory already exists - use --force to recreate or --merge to merge into it"
+        ));
+    }
 
-        let rel_path =
-            hb.0.render_template(&path.strip_prefix(&root_dir)?.to_str().unwrap(), &hb.1)
-                .map_err(|e| anyhow::anyhow!(e))?;
-        if rel_path == ".ranger.yaml" {
-            continue;
+    let mut actions = plan.actions(out_path_root, options.merge.as_ref())?;
+    // the answers file is owned by ranger and always replaced
+    for (entry, action) in plan.entries.iter().zip(actions.iter_mut()) {
+        if entry.path == Path::new(answers::FILE_NAME)
+            && matches!(action, render::Action::Conflict | render::Action::Backup)
+        {
+            *action = render::Action::Overwrite;
         }
-        let out_path = Path::join(out_path_root, rel_path);
-
-        if path.is_dir() {
-            std::fs::create_dir_all(out_path)?;
-        } else {
-            let content = std::fs::read_to_string(path)?;
-            let rendered = hb.0.render_template(&content, &hb.1).map_err(|e| anyhow::anyhow!(e))?;
-            std::fs::write(out_path, rendered)?;
+    }
+    if options.dry_run {
+        plan.print(&actions);
+        if options.force {
+            for path in plan.deletions(out_path_root)? {
+                let suffix = if Path::join(out_path_root, &path).is_dir() {
+                    "/"
+                } else {
+                    ""
+                };
+                println!("{:<9} {:>10}  {}{}", "delete", "-", path.display(), suffix);
+            }
+        }
+        if exists {
+            eprintln!("note: the output directory already exists, generating requires --force or --merge");
         }
+        return Ok(());
+    }
+    if let Some(strategy) = &options.merge {
+        plan.resolve_conflicts(&mut actions, strategy, backend).await?;
     }
 
-    Ok(())
+    plan.commit(&actions, out_path_root, options.merge.is_some())
 }
@@ -0,0 +1,1489 @@
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+        HashSet,
+    },
+    path::{
+        Component,
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::Result;
+use complate::render::Resolve;
+use handlebars::template::{
+    Parameter,
+    TemplateElement,
+};
+
+use crate::{
+    args::ConflictStrategy,
+    blueprint::Blueprint,
+    error::Error,
+};
+
+#[derive(Debug)]
+pub enum Content {
+    Directory,
+    File(Vec<u8>),
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub content: Content,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Action {
+    Create,
+    Overwrite,
+    Backup,
+    Skip,
+    Conflict,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            | Action::Create => "create",
+            | Action::Overwrite => "overwrite",
+            | Action::Backup => "backup",
+            | Action::Skip => "skip",
+            | Action::Conflict => "conflict",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Plan {
+    pub entries: Vec<Entry>,
+    pub values: HashMap<String, String>,
+    /// Where the values that were not overridden came from (`default` or
+    /// `prompt`).
+    pub origins: HashMap<String, &'static str>,
+}
+
+impl Plan {
+    pub fn actions(&self, out_path_root: &Path, merge: Option<&ConflictStrategy>) -> Result<Vec<Action>> {
+        let mut actions = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            ensure_contained(out_path_root, &entry.path)?;
+            let out_path = Path::join(out_path_root, &entry.path);
+            let action = match (&entry.content, out_path.exists()) {
+                | (_, false) => Action::Create,
+                | (Content::Directory, true) if out_path.is_dir() => Action::Skip,
+                | (Content::File(_), true) if out_path.is_dir() => {
+                    return Err(anyhow::anyhow!(
+                        "can not write file {}, a directory with the same name exists",
+                        entry.path.display()
+                    ))
+                },
+                | (Content::Directory, true) => {
+                    return Err(anyhow::anyhow!(
+                        "can not create directory {}, a file with the same name exists",
+                        entry.path.display()
+                    ))
+                },
+                | (Content::File(data), true) => {
+                    match merge {
+                        | None => Action::Overwrite,
+                        | Some(_) if &std::fs::read(&out_path)? == data => Action::Skip,
+                        | Some(ConflictStrategy::Skip) => Action::Skip,
+                        | Some(ConflictStrategy::Overwrite) => Action::Overwrite,
+                        | Some(ConflictStrategy::Backup) => Action::Backup,
+                        | Some(ConflictStrategy::Prompt) | Some(ConflictStrategy::Fail) => Action::Conflict,
+                    }
+                },
+            };
+            actions.push(action);
+        }
+        Ok(actions)
+    }
+
+    pub async fn resolve_conflicts(
+        &self,
+        actions: &mut [Action],
+        strategy: &ConflictStrategy,
+        backend: &complate::render::Backend,
+    ) -> Result<()> {
+        let conflicts = self
+            .entries
+            .iter()
+            .zip(actions.iter())
+            .filter(|(_, action)| **action == Action::Conflict)
+            .map(|(entry, _)| entry.path.display().to_string())
+            .collect::<Vec<_>>();
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+
+        match strategy {
+            | ConflictStrategy::Prompt => {
+                let mut options = BTreeMap::new();
+                for (key, display) in [("0", "skip"), ("1", "overwrite"), ("2", "backup")] {
+                    options.insert(key.to_owned(), complate::config::Option {
+                        display: display.to_owned(),
+                        value: complate::config::OptionValue::Static(display.to_owned()),
+                    });
+                }
+                let input = backend.to_input(&complate::render::ShellTrust::None)?;
+                for (entry, action) in self.entries.iter().zip(actions.iter_mut()) {
+                    if *action != Action::Conflict {
+                        continue;
+                    }
+                    *action = match input
+                        .select(&format!("{} already exists", entry.path.display()), &options)
+                        .await?
+                        .as_str()
+                    {
+                        | "overwrite" => Action::Overwrite,
+                        | "backup" => Action::Backup,
+                        | _ => Action::Skip,
+                    };
+                }
+                Ok(())
+            },
+            | _ => {
+                Err(anyhow::anyhow!(
+                    "conflicting files already exist:\n{}",
+                    conflicts.join("\n")
+                ))
+            },
+        }
+    }
+
+    /// The files and directories of an existing output directory that are not
+    /// part of the plan and are deleted when it is replaced. Directories are
+    /// listed without their content.
+    pub fn deletions(&self, out_path_root: &Path) -> Result<Vec<PathBuf>> {
+        if !out_path_root.is_dir() {
+            return Ok(Vec::new());
+        }
+        let paths = self.entries.iter().map(|e| e.path.as_path()).collect::<HashSet<_>>();
+        let mut deletions = Vec::new();
+        let mut walk = walkdir::WalkDir::new(out_path_root).min_depth(1).sort_by_file_name().into_iter();
+        while let Some(entry) = walk.next() {
+            let entry = entry?;
+            let path = entry.path().strip_prefix(out_path_root)?;
+            if paths.contains(path) {
+                continue;
+            }
+            if entry.file_type().is_dir() {
+                walk.skip_current_dir();
+            }
+            deletions.push(path.to_path_buf());
+        }
+        Ok(deletions)
+    }
+
+    pub fn print(&self, actions: &[Action]) {
+        for (entry, action) in self.entries.iter().zip(actions) {
+            match &entry.content {
+                | Content::Directory => println!("{:<9} {:>10}  {}/", action, "-", entry.path.display()),
+                | Content::File(data) => println!("{:<9} {:>10}  {}", action, data.len(), entry.path.display()),
+            }
+        }
+    }
+
+    /// Prints every variable value and where it came from to stderr.
+    /// `override_origins` describes the values that were passed in.
+    pub fn print_values(&self, override_origins: &HashMap<String, String>) {
+        let mut names = self.values.keys().collect::<Vec<_>>();
+        names.sort();
+        let origin = |name: &str| {
+            self.origins
+                .get(name)
+                .map(|o| o.to_string())
+                .or_else(|| override_origins.get(name).cloned())
+                .unwrap_or_default()
+        };
+        let name_width = names.iter().map(|n| n.len()).max().unwrap_or_default();
+        let origin_width = names.iter().map(|n| origin(n).len()).max().unwrap_or_default();
+        for name in names {
+            eprintln!(
+                "{:<name_width$}  {:<origin_width$}  {}",
+                name,
+                origin(name),
+                self.values[name].escape_debug(),
+                name_width = name_width,
+                origin_width = origin_width
+            );
+        }
+    }
+
+    /// Writes the plan into a staging directory next to the output directory
+    /// and only moves it into place once every file was written successfully,
+    /// so failures leave the output directory untouched. Without `merge`, the
+    /// output directory is replaced as a whole, otherwise the staged entries
+    /// are moved into it one by one.
+    pub fn commit(&self, actions: &[Action], out_path_root: &Path, merge: bool) -> Result<()> {
+        let out_path_root = absolute(out_path_root)?;
+        let staging = sibling(&out_path_root, "staging")?;
+        if let Err(e) = self.write(actions, &out_path_root, &staging, merge) {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+
+        if !merge {
+            let previous = if out_path_root.exists() {
+                let previous = sibling(&out_path_root, "previous")?;
+                std::fs::rename(&out_path_root, &previous)?;
+                Some(previous)
+            } else {
+                None
+            };
+            return match std::fs::rename(&staging, &out_path_root) {
+                | Ok(_) => {
+                    if let Some(previous) = previous {
+                        std::fs::remove_dir_all(previous)?;
+                    }
+                    Ok(())
+                },
+                | Err(e) => {
+                    if let Some(previous) = previous {
+                        std::fs::rename(previous, &out_path_root)?;
+                    }
+                    let _ = std::fs::remove_dir_all(&staging);
+                    Err(e.into())
+                },
+            };
+        }
+
+        let result = (|| {
+            std::fs::create_dir_all(&out_path_root)?;
+            for (entry, action) in self.entries.iter().zip(actions) {
+                let out_path = Path::join(&out_path_root, &entry.path);
+                match (&entry.content, action) {
+                    | (_, Action::Skip) => {},
+                    | (Content::Directory, _) => std::fs::create_dir_all(out_path)?,
+                    | (Content::File(_), _) => {
+                        ensure_contained(&out_path_root, &entry.path)?;
+                        if *action == Action::Backup {
+                            std::fs::rename(&out_path, backup_path(&out_path))?;
+                        }
+                        std::fs::rename(Path::join(&staging, &entry.path), out_path)?;
+                    },
+                }
+            }
+            Ok::<_, anyhow::Error>(())
+        })();
+        let _ = std::fs::remove_dir_all(&staging);
+        result
+    }
+
+    fn write(&self, actions: &[Action], out_path_root: &Path, staging: &Path, merge: bool) -> Result<()> {
+        std::fs::create_dir_all(staging)?;
+        for (entry, action) in self.entries.iter().zip(actions) {
+            ensure_contained(out_path_root, &entry.path)?;
+            let staged_path = Path::join(staging, &entry.path);
+            match (&entry.content, action) {
+                // when replacing the output directory, existing entries have to be staged as well
+                | (_, Action::Skip) if merge => {},
+                | (_, Action::Conflict) => {
+                    return Err(anyhow::anyhow!("unresolved conflict: {}", entry.path.display()));
+                },
+                | (Content::Directory, _) => std::fs::create_dir_all(staged_path)?,
+                | (Content::File(data), _) => {
+                    if let Some(parent) = staged_path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(staged_path, data)?
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The first of `<name>.orig`, `<name>.orig.1`, `<name>.orig.2`, ... that
+/// does not exist yet, so earlier backups are never overwritten.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".orig");
+    let mut candidate = PathBuf::from(&backup);
+    let mut n = 1;
+    while candidate.symlink_metadata().is_ok() {
+        candidate = PathBuf::from(format!("{}.{}", backup.to_string_lossy(), n));
+        n += 1;
+    }
+    candidate
+}
+
+pub fn absolute(path: &Path) -> Result<PathBuf> {
+    match std::fs::canonicalize(path) {
+        | Ok(path) => Ok(path),
+        | Err(_) => Ok(std::path::absolute(path)?),
+    }
+}
+
+/// A unique path in the same directory as `path`, so that it can be renamed
+/// into place without crossing file systems.
+pub fn sibling(path: &Path, purpose: &str) -> Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| anyhow::anyhow!("invalid output directory {}", path.display()))?;
+    let parent = path.parent().ok_or_else(|| anyhow::anyhow!("invalid output directory {}", path.display()))?;
+    std::fs::create_dir_all(parent)?;
+    Ok(parent.join(format!(
+        ".{}.ranger-{}-{}",
+        name.to_string_lossy(),
+        purpose,
+        uuid::Uuid::new_v4()
+    )))
+}
+
+pub async fn render(
+    bp: &Blueprint,
+    value_overrides: &HashMap<String, String>,
+    root_dir: &Path,
+    backend: &complate::render::Backend,
+    shell_trust: &complate::render::ShellTrust,
+    strict: bool,
+) -> Result<Plan> {
+    // strict mode is on unless the template opts out, `strict` enforces it
+    let hb = make_handlebars(bp, shell_trust, strict || bp.template.strict.unwrap_or(true))?;
+    // `when` and rules are never strict, references to skipped variables are false
+    let predicates = make_handlebars(bp, shell_trust, false)?;
+    let (values, origins) = populate(bp, value_overrides, backend, shell_trust, &predicates).await?;
+
+    let mut ctx = context(bp, &values)?;
+    let mut computed = values.clone();
+    for name in computed_order(bp)? {
+        let expression = &bp.template.computed.as_ref().unwrap()[name];
+        let value = hb.render_template(expression, &ctx).map_err(|e| anyhow::anyhow!("computed {}: {}", name, e))?;
+        computed.insert(name.to_owned(), value);
+        ctx = context(bp, &computed)?;
+    }
+
+    let copy = glob_set(bp.template.copy.iter().flatten().map(|g| g.as_str()))?;
+
+    // rules that evaluate to false exclude their matches just like ignored paths
+    let mut excluded = Vec::new();
+    for (pattern, expression) in bp.template.rules.iter().flatten() {
+        let result =
+            predicates.render_template(expression, &ctx).map_err(|e| anyhow::anyhow!("rule {}: {}", pattern, e))?;
+        if !is_truthy(&result) {
+            excluded.push(pattern.as_str());
+        }
+    }
+    let excluded = glob_set(excluded.into_iter())?;
+
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut walker = walk_template(root_dir, bp)?;
+    while let Some(w) = walker.next() {
+        let entry = w?;
+        let path = entry.path();
+        let template_path = path.strip_prefix(root_dir)?;
+        if excluded.is_match(template_path) {
+            if entry.file_type().is_dir() {
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+
+        // a path segment that renders to an empty string skips the entry (and
+        // everything below it)
+        let mut rel_path = PathBuf::new();
+        for segment in template_path.iter() {
+            let rendered = match hb.render_template(segment.to_str().unwrap(), &ctx) {
+                | Ok(rendered) => rendered,
+                | Err(e) => {
+                    errors.push(render_error(&format!("{} (path)", template_path.display()), &e));
+                    String::new()
+                },
+            };
+            if rendered.is_empty() {
+                rel_path.clear();
+                break;
+            }
+            rel_path.push(rendered);
+        }
+        if rel_path.as_os_str().is_empty() {
+            if entry.file_type().is_dir() {
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+        let unsafe_path = |reason: &str| {
+            Error::UnsafePath {
+                entry: template_path.display().to_string(),
+                path: rel_path.display().to_string(),
+                reason: reason.to_owned(),
+            }
+        };
+        let rel_path = normalize(&rel_path).map_err(&unsafe_path)?;
+        if entry.path_is_symlink() && !std::fs::canonicalize(path)?.starts_with(std::fs::canonicalize(root_dir)?) {
+            return Err(unsafe_path("symlink points outside of the template").into());
+        }
+
+        let content = if path.is_dir() {
+            Content::Directory
+        } else {
+            let data = std::fs::read(path)?;
+            if is_binary(&data) || copy.is_match(template_path) {
+                Content::File(data)
+            } else {
+                let content = String::from_utf8(data)?;
+                match hb.render_template(&content, &ctx) {
+                    | Ok(rendered) => Content::File(rendered.into_bytes()),
+                    | Err(e) => {
+                        errors.extend(render_errors(&template_path.display().to_string(), &content, &ctx, &e));
+                        continue;
+                    },
+                }
+            }
+        };
+        entries.push(Entry {
+            path: rel_path,
+            content,
+        });
+    }
+
+    if !errors.is_empty() {
+        return Err(anyhow::anyhow!("failed to render the template:\n{}", errors.join("\n")));
+    }
+    Ok(Plan {
+        entries,
+        values,
+        origins,
+    })
+}
+
+/// Formats a render error as `file:line:column: reason`.
+fn render_error(file: &str, e: &handlebars::RenderError) -> String {
+    let (position, reason) = match e.reason() {
+        | handlebars::RenderErrorReason::TemplateError(e) => (e.pos(), e.reason().to_string()),
+        | handlebars::RenderErrorReason::MissingVariable(Some(path)) => {
+            (e.line_no.zip(e.column_no), format!("undefined variable \"{}\"", path))
+        },
+        | reason => (e.line_no.zip(e.column_no), reason.to_string()),
+    };
+    match position {
+        | Some((line, column)) => format!("{}:{}:{}: {}", file, line, column, reason),
+        | None => format!("{}: {}", file, reason),
+    }
+}
+
+/// Formats the errors of a file that failed to render. Strict mode stops at
+/// the first undefined variable, so every other undefined variable of the file
+/// is looked up and reported as well.
+fn render_errors(file: &str, source: &str, ctx: &serde_json::Value, e: &handlebars::RenderError) -> Vec<String> {
+    if !matches!(e.reason(), handlebars::RenderErrorReason::MissingVariable(_)) {
+        return vec![render_error(file, e)];
+    }
+    let (Ok(template), Ok(reference)) = (
+        handlebars::Template::compile(source),
+        regex::Regex::new(VARIABLE_REFERENCE),
+    ) else {
+        return vec![render_error(file, e)];
+    };
+    let mut undefined = Vec::new();
+    undefined_references(&template, ctx, &reference, &mut undefined);
+    if undefined.is_empty() {
+        return vec![render_error(file, e)];
+    }
+    undefined
+        .into_iter()
+        .map(|(path, (line, column))| format!("{}:{}:{}: undefined variable \"vars.{}\"", file, line, column, path))
+        .collect()
+}
+
+/// Collects the variables that strict mode rejects in `template` (plain
+/// `{{vars.x}}` expressions and `#each` blocks) and that are not defined in
+/// `ctx`, together with their line and column.
+fn undefined_references(
+    template: &handlebars::template::Template,
+    ctx: &serde_json::Value,
+    reference: &regex::Regex,
+    undefined: &mut Vec<(String, (usize, usize))>,
+) {
+    for (element, mapping) in template.elements.iter().zip(&template.mapping) {
+        let mut check = |p: &Parameter| {
+            if let Some(captures) = p.as_name().and_then(|name| reference.captures(name)) {
+                let pointer = format!("/vars/{}", captures[1].replace('.', "/"));
+                if ctx.pointer(&pointer).is_none() {
+                    undefined.push((captures[1].to_owned(), (mapping.0, mapping.1)));
+                }
+            }
+        };
+        let templates = match element {
+            | TemplateElement::Expression(h) | TemplateElement::HtmlExpression(h)
+                if h.params.is_empty() && h.hash.is_empty() =>
+            {
+                check(&h.name);
+                vec![]
+            },
+            | TemplateElement::HelperBlock(h) => {
+                if h.name.as_name() == Some("each") {
+                    h.params.iter().take(1).for_each(&mut check);
+                }
+                vec![&h.template, &h.inverse]
+            },
+            | TemplateElement::DecoratorBlock(d) | TemplateElement::PartialBlock(d) => vec![&d.template],
+            | _ => vec![],
+        };
+        for template in templates.into_iter().flatten() {
+            undefined_references(template, ctx, reference, undefined);
+        }
+    }
+}
+
+fn make_handlebars(
+    bp: &Blueprint,
+    shell_trust: &complate::render::ShellTrust,
+    strict: bool,
+) -> Result<handlebars::Handlebars<'static>> {
+    let mut hb = handlebars::Handlebars::new();
+    hb.register_escape_fn(handlebars::no_escape);
+    hb.set_strict_mode(strict);
+    crate::helpers::register(&mut hb);
+
+    if bp.template.helpers.as_ref().is_some_and(|h| !h.is_empty())
+        && shell_trust != &complate::render::ShellTrust::Ultimate
+    {
+        return Err(anyhow::anyhow!("need trust for executing helper functions"));
+    }
+
+    for (name, cmd) in bp.template.helpers.iter().flatten() {
+        let cmd = cmd.to_owned();
+        let h_func = move |h: &handlebars::Helper,
+                           _: &handlebars::Handlebars,
+                           _: &handlebars::Context,
+                           _: &mut handlebars::RenderContext,
+                           out: &mut dyn handlebars::Output|
+              -> handlebars::HelperResult {
+            let value = h
+                .param(0)
+                .and_then(|p| p.value().as_str())
+                .ok_or(handlebars::RenderErrorReason::InvalidParamType("string"))?;
+            let output = std::process::Command::new("sh").arg("-c").arg(&cmd).env("VALUE", value).output()?;
+            if !output.status.success() {
+                return Err(handlebars::RenderErrorReason::Other(format!("helper command failed: {}", cmd)).into());
+            }
+            out.write(&String::from_utf8(output.stdout)?)?;
+            Ok(())
+        };
+        hb.register_helper(name, Box::new(h_func));
+    }
+
+    Ok(hb)
+}
+
+/// Resolves every variable that is not overridden, in declaration order.
+/// Variables whose `when` predicate (evaluated against the variables resolved
+/// so far with the non-strict `predicates`) is false are skipped. Invalid
+/// prompted values are asked for again in interactive mode, otherwise all
+/// invalid variables are reported at once.
+async fn populate(
+    bp: &Blueprint,
+    value_overrides: &HashMap<String, String>,
+    backend: &complate::render::Backend,
+    shell_trust: &complate::render::ShellTrust,
+    predicates: &handlebars::Handlebars<'_>,
+) -> Result<(HashMap<String, String>, HashMap<String, &'static str>)> {
+    let variables = bp.template.variables.as_ref();
+    let mut origins = HashMap::new();
+    let mut values = value_overrides
+        .iter()
+        .filter(|(k, _)| variables.is_none_or(|v| !v.contains_key(*k)))
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .collect::<HashMap<_, _>>();
+    let mut errors = Vec::new();
+    for (name, variable) in variables.into_iter().flatten() {
+        if let Some(value) = value_overrides.get(name) {
+            match variable.validate(value) {
+                | Ok(_) => {
+                    values.insert(name.to_owned(), value.to_owned());
+                },
+                | Err(e) => errors.push(format!("{}: {}", name, e)),
+            }
+            continue;
+        }
+
+        if let Some(when) = &variable.when {
+            match predicates.render_template(when, &context(bp, &values)?) {
+                | Ok(result) if is_truthy(&result) => {},
+                | Ok(_) => continue,
+                | Err(e) => {
+                    errors.push(format!("{}: when: {}", name, e));
+                    continue;
+                },
+            }
+        }
+
+        loop {
+            let value = match variable.definition.execute(shell_trust, backend).await {
+                | Ok(v) => v,
+                | Err(e) => {
+                    errors.push(format!("{}: {}", name, e));
+                    break;
+                },
+            };
+            match variable.validate(&value) {
+                | Ok(_) => {
+                    values.insert(name.to_owned(), value);
+                    origins.insert(
+                        name.to_owned(),
+                        if variable.is_interactive() { "prompt" } else { "default" },
+                    );
+                },
+                | Err(e) if matches!(backend, complate::render::Backend::CLI) && variable.is_interactive() => {
+                    eprintln!("invalid value for {}: {}", name, e);
+                    continue;
+                },
+                | Err(e) => errors.push(format!("{}: {}", name, e)),
+            }
+            break;
+        }
+    }
+
+    if !errors.is_empty() {
+        errors.sort();
+        return Err(anyhow::anyhow!("invalid variable values:\n{}", errors.join("\n")));
+    }
+    Ok((values, origins))
+}
+
+/// Orders the computed variables so that every variable comes after the
+/// computed variables its expression references.
+fn computed_order(bp: &Blueprint) -> Result<Vec<&String>> {
+    let Some(computed) = &bp.template.computed else {
+        return Ok(Vec::new());
+    };
+    let reference = regex::Regex::new(VARIABLE_REFERENCE)?;
+    let dependencies = computed
+        .iter()
+        .map(|(name, expression)| {
+            let deps = reference
+                .captures_iter(expression)
+                .flat_map(|c| {
+                    let path = c[1].to_owned();
+                    computed.keys().filter(move |other| path == **other || path.starts_with(&format!("{}.", other)))
+                })
+                .collect::<Vec<_>>();
+            (name, deps)
+        })
+        .collect::<HashMap<_, _>>();
+
+    fn visit<'a>(
+        name: &'a String,
+        dependencies: &HashMap<&'a String, Vec<&'a String>>,
+        stack: &mut Vec<&'a String>,
+        order: &mut Vec<&'a String>,
+    ) -> Result<()> {
+        if order.contains(&name) {
+            return Ok(());
+        }
+        if let Some(pos) = stack.iter().position(|n| *n == name) {
+            let cycle = stack[pos..].iter().chain([&name]).map(|n| n.as_str()).collect::<Vec<_>>();
+            return Err(anyhow::anyhow!("cyclic computed variables: {}", cycle.join(" -> ")));
+        }
+        stack.push(name);
+        for dep in &dependencies[name] {
+            visit(dep, dependencies, stack, order)?;
+        }
+        stack.pop();
+        order.push(name);
+        Ok(())
+    }
+
+    let mut names = computed.keys().collect::<Vec<_>>();
+    names.sort();
+    let mut order = Vec::new();
+    for name in names {
+        visit(name, &dependencies, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Builds the handlebars context with every variable parsed into its declared
+/// type and nested under `vars` by its dotted name. Variables are added in
+/// declaration order followed by all others sorted by name, so that the
+/// result never depends on the order of `values`.
+fn context(bp: &Blueprint, values: &HashMap<String, String>) -> Result<serde_json::Value> {
+    let declared = bp.template.variables.as_ref();
+    let mut names = values.keys().collect::<Vec<_>>();
+    names.sort_by_key(|name| {
+        (
+            declared.and_then(|v| v.get_index_of(*name)).unwrap_or(usize::MAX),
+            *name,
+        )
+    });
+
+    let mut vars = serde_json::Map::new();
+    for name in names {
+        let raw = &values[name];
+        let value = match bp.template.variables.as_ref().and_then(|v| v.get(name)) {
+            | Some(variable) => variable.parse(raw)?,
+            | None => serde_json::Value::String(raw.to_owned()),
+        };
+
+        let mut segments = name.split('.').peekable();
+        let mut parent = &mut vars;
+        while let Some(segment) = segments.next() {
+            if segments.peek().is_none() {
+                if parent.insert(segment.to_owned(), value).is_some() {
+                    return Err(anyhow::anyhow!(
+                        "variable {} overlaps with another variable, a variable can not have nested variables",
+                        name
+                    ));
+                }
+                break;
+            }
+            parent = parent
+                .entry(segment)
+                .or_insert(serde_json::Value::Object(serde_json::Map::new()))
+                .as_object_mut()
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "variable {} overlaps with another variable, a variable can not have nested variables",
+                        name
+                    )
+                })?;
+        }
+    }
+
+    let mut context = serde_json::Map::new();
+    context.insert("vars".to_owned(), serde_json::Value::Object(vars));
+    Ok(serde_json::Value::Object(context))
+}
+
+/// Resolves `.` and `..` in a rendered relative path. Absolute paths and paths
+/// escaping the output root are rejected.
+fn normalize(path: &Path) -> Result<PathBuf, &'static str> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            | Component::Normal(c) => normalized.push(c),
+            | Component::CurDir => {},
+            | Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err("path escapes the output directory");
+                }
+            },
+            | Component::RootDir | Component::Prefix(_) => return Err("absolute paths are not allowed"),
+        }
+    }
+    if normalized.as_os_str().is_empty() {
+        return Err("path resolves to the output directory itself");
+    }
+    Ok(normalized)
+}
+
+/// Makes sure that no existing symlink within the output directory redirects
+/// `rel_path` to a location outside of it.
+pub fn ensure_contained(out_path_root: &Path, rel_path: &Path) -> Result<()> {
+    let Ok(root) = std::fs::canonicalize(out_path_root) else {
+        return Ok(()); // nothing exists yet, so nothing can be followed
+    };
+    let mut current = out_path_root.to_path_buf();
+    for component in rel_path.components() {
+        current.push(component);
+        let Ok(meta) = std::fs::symlink_metadata(&current) else {
+            break;
+        };
+        if meta.file_type().is_symlink()
+            && !std::fs::canonicalize(&current).is_ok_and(|target| target.starts_with(&root))
+        {
+            return Err(Error::UnsafePath {
+                entry: rel_path.display().to_string(),
+                path: current.display().to_string(),
+                reason: "symlink in the output directory points outside of it".to_owned(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Matches references to variables (`vars.app.name`) in template sources.
+pub const VARIABLE_REFERENCE: &str = r"vars\.([\w\-]+(?:\.[\w\-]+)*)";
+
+pub const DEFAULT_IGNORE: &[&str] = &[".git"];
+
+/// Collects the variables referenced by the expressions of a compiled
+/// template (plain text is ignored) together with the line and column of the
+/// expression.
+pub fn template_references(
+    template: &handlebars::template::Template,
+    reference: &regex::Regex,
+    references: &mut Vec<(String, (usize, usize))>,
+) {
+    for (element, mapping) in template.elements.iter().zip(&template.mapping) {
+        element_references(element, (mapping.0, mapping.1), reference, references);
+    }
+}
+
+fn element_references(
+    element: &TemplateElement,
+    position: (usize, usize),
+    reference: &regex::Regex,
+    references: &mut Vec<(String, (usize, usize))>,
+) {
+    let mut parameter = |p: &Parameter| {
+        match p {
+            | Parameter::Subexpression(s) => element_references(&s.element, position, reference, references),
+            | p => {
+                if let Some(captures) = p.as_name().and_then(|name| reference.captures(name)) {
+                    references.push((captures[1].to_owned(), position));
+                }
+            },
+        }
+    };
+    let templates = match element {
+        | TemplateElement::RawString(_) | TemplateElement::Comment(_) => return,
+        | TemplateElement::HtmlExpression(h) | TemplateElement::Expression(h) | TemplateElement::HelperBlock(h) => {
+            std::iter::once(&h.name).chain(&h.params).chain(h.hash.values()).for_each(&mut parameter);
+            vec![&h.template, &h.inverse]
+        },
+        | TemplateElement::DecoratorExpression(d)
+        | TemplateElement::DecoratorBlock(d)
+        | TemplateElement::PartialExpression(d)
+        | TemplateElement::PartialBlock(d) => {
+            std::iter::once(&d.name).chain(&d.params).chain(d.hash.values()).for_each(&mut parameter);
+            vec![&d.template]
+        },
+    };
+    for template in templates.into_iter().flatten() {
+        template_references(template, reference, references);
+    }
+}
+
+/// Walks the files and folders of a template in file name order, leaving out
+/// the blueprint and everything matched by the default or the template's
+/// ignore globs.
+pub fn walk_template<'a>(
+    root_dir: &'a Path,
+    bp: &Blueprint,
+) -> Result<walkdir::FilterEntry<walkdir::IntoIter, impl FnMut(&walkdir::DirEntry) -> bool+'a>> {
+    let ignore =
+        glob_set(DEFAULT_IGNORE.iter().copied().chain(bp.template.ignore.iter().flatten().map(|g| g.as_str())))?;
+    Ok(
+        walkdir::WalkDir::new(root_dir).min_depth(1).sort_by_file_name().into_iter().filter_entry(move |e| {
+            e.path()
+                .strip_prefix(root_dir)
+                .is_ok_and(|p| p != Path::new(crate::blueprint::FILE_NAME) && !ignore.is_match(p))
+        }),
+    )
+}
+
+/// Builds a glob set with gitignore-like semantics: patterns without a slash
+/// match at any depth and `dir/**` also matches `dir` itself.
+pub fn glob_set<'a>(patterns: impl Iterator<Item=&'a str>) -> Result<globset::GlobSet> {
+    let mut builder = globset::GlobSetBuilder::new();
+    for pattern in patterns {
+        let pattern = pattern.trim_end_matches('/');
+        let pattern = if pattern.contains('/') {
+            pattern.to_owned()
+        } else {
+            format!("**/{}", pattern)
+        };
+        if let Some(dir) = pattern.strip_suffix("/**") {
+            builder.add(globset::GlobBuilder::new(dir).literal_separator(true).build()?);
+        }
+        builder.add(globset::GlobBuilder::new(&pattern).literal_separator(true).build()?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Empty strings, `false`, `0` and `no` are considered false.
+pub fn is_truthy(value: &str) -> bool {
+    !matches!(value.trim().to_lowercase().as_str(), "" | "false" | "0" | "no")
+}
+
+/// Files that contain a NUL byte in their first 8KiB or are not valid UTF-8 are
+/// treated as binary and copied verbatim.
+pub fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(8192)].contains(&0) || std::str::from_utf8(data).is_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    const BLUEPRINT: &str = r#"
+version: "0.2"
+template:
+  variables:
+    name:
+      type: string
+      static: default
+"#;
+
+    fn template(blueprint: &str, files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(crate::blueprint::FILE_NAME), blueprint).unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    async fn render_with(root_dir: &Path, name: &str) -> Result<Plan> {
+        render_values(root_dir, HashMap::from([("name".to_owned(), name.to_owned())])).await
+    }
+
+    async fn render_values(root_dir: &Path, values: HashMap<String, String>) -> Result<Plan> {
+        let bp = Blueprint::load(root_dir)?;
+        render(
+            &bp,
+            &values,
+            root_dir,
+            &complate::render::Backend::Headless,
+            &complate::render::ShellTrust::None,
+            false,
+        )
+        .await
+    }
+
+    #[test]
+    fn normalize_resolves_relative_components() {
+        assert_eq!(normalize(Path::new("a/./b/../c")).unwrap(), PathBuf::from("a/c"));
+        assert_eq!(
+            normalize(Path::new("a/..")),
+            Err("path resolves to the output directory itself")
+        );
+        assert_eq!(normalize(Path::new("../a")), Err("path escapes the output directory"));
+        assert_eq!(
+            normalize(Path::new("a/../../b")),
+            Err("path escapes the output directory")
+        );
+        assert_eq!(
+            normalize(Path::new("/etc/passwd")),
+            Err("absolute paths are not allowed")
+        );
+    }
+
+    #[tokio::test]
+    async fn variable_values_can_not_escape_the_output_directory() {
+        let dir = template(BLUEPRINT, &[("{{vars.name}}/file.txt", "x")]);
+        assert!(render_with(dir.path(), "ok").await.is_ok());
+        for value in ["..", "../..", "a/../..", "/tmp"] {
+            let err = render_with(dir.path(), value).await.unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(Error::UnsafePath { .. })),
+                "{}: {}",
+                value,
+                err
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn symlinks_in_the_template_must_stay_inside_of_it() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "secret").unwrap();
+
+        let dir = template(BLUEPRINT, &[("file.txt", "x")]);
+        symlink(dir.path().join("file.txt"), dir.path().join("inside")).unwrap();
+        assert!(render_with(dir.path(), "ok").await.is_ok());
+
+        symlink(outside.path().join("secret"), dir.path().join("outside")).unwrap();
+        let err = render_with(dir.path(), "ok").await.unwrap_err();
+        assert!(
+            err.to_string().contains("symlink points outside of the template"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn symlinks_in_the_output_directory_must_stay_inside_of_it() {
+        let outside = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        std::fs::create_dir(out.path().join("dir")).unwrap();
+        symlink(out.path().join("dir"), out.path().join("inside")).unwrap();
+        symlink(outside.path(), out.path().join("outside")).unwrap();
+
+        assert!(ensure_contained(out.path(), Path::new("dir/file.txt")).is_ok());
+        assert!(ensure_contained(out.path(), Path::new("inside/file.txt")).is_ok());
+        assert!(ensure_contained(out.path(), Path::new("missing/file.txt")).is_ok());
+        let err = ensure_contained(out.path(), Path::new("outside/file.txt")).unwrap_err();
+        assert!(err.to_string().contains("points outside of it"), "{}", err);
+        assert!(ensure_contained(&out.path().join("not-created"), Path::new("a")).is_ok());
+    }
+
+    /// A plan of files, `None` content denotes a directory.
+    fn plan(entries: &[(&str, Option<&str>)]) -> Plan {
+        Plan {
+            entries: entries
+                .iter()
+                .map(|(path, content)| {
+                    Entry {
+                        path: PathBuf::from(path),
+                        content: match content {
+                            | Some(data) => Content::File(data.as_bytes().to_vec()),
+                            | None => Content::Directory,
+                        },
+                    }
+                })
+                .collect(),
+            values: HashMap::new(),
+            origins: HashMap::new(),
+        }
+    }
+
+    /// Every file below `dir` with its content, relative to `dir`.
+    fn tree(dir: &Path) -> BTreeMap<String, Vec<u8>> {
+        walkdir::WalkDir::new(dir)
+            .into_iter()
+            .map(|e| e.unwrap())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| {
+                (
+                    e.path().strip_prefix(dir).unwrap().display().to_string(),
+                    std::fs::read(e.path()).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn commit_replaces_the_output_directory() {
+        let parent = tempfile::tempdir().unwrap();
+        let out = parent.path().join("out");
+        std::fs::create_dir_all(out.join("old")).unwrap();
+        std::fs::write(out.join("old/file.txt"), "old").unwrap();
+
+        let plan = plan(&[("dir", None), ("dir/file.txt", Some("new")), ("top.txt", Some("top"))]);
+        let actions = plan.actions(&out, None).unwrap();
+        plan.commit(&actions, &out, false).unwrap();
+        assert_eq!(
+            tree(&out),
+            BTreeMap::from([
+                ("dir/file.txt".to_owned(), b"new".to_vec()),
+                ("top.txt".to_owned(), b"top".to_vec()),
+            ])
+        );
+        assert_eq!(file_names(parent.path()), vec!["out"]);
+    }
+
+    #[test]
+    fn deletions_are_the_entries_not_in_the_plan() {
+        let out = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(out.path().join("dir/old")).unwrap();
+        std::fs::create_dir_all(out.path().join("gone/sub")).unwrap();
+        for file in [
+            "dir/file.txt",
+            "dir/old.txt",
+            "dir/old/file.txt",
+            "gone/sub/file.txt",
+            "top.txt",
+        ] {
+            std::fs::write(out.path().join(file), "old").unwrap();
+        }
+
+        let plan = plan(&[("dir", None), ("dir/file.txt", Some("new")), ("top.txt", Some("new"))]);
+        assert_eq!(plan.deletions(out.path()).unwrap(), vec![
+            PathBuf::from("dir/old"),
+            PathBuf::from("dir/old.txt"),
+            PathBuf::from("gone"),
+        ]);
+        assert!(plan.deletions(&out.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_commit_leaves_the_output_directory_untouched() {
+        let parent = tempfile::tempdir().unwrap();
+        let out = parent.path().join("out");
+        std::fs::create_dir_all(out.join("dir")).unwrap();
+        std::fs::write(out.join("dir/file.txt"), "old").unwrap();
+        std::fs::write(out.join("top.txt"), [0, 159, 146, 150]).unwrap();
+        let before = tree(&out);
+
+        // `a/b` can not be staged below the file `a`
+        let plan = plan(&[("top.txt", Some("new")), ("a", Some("file")), ("a/b", Some("file"))]);
+        let actions = plan.actions(&out, None).unwrap();
+        assert!(plan.commit(&actions, &out, false).is_err());
+        assert_eq!(tree(&out), before);
+        assert_eq!(file_names(parent.path()), vec!["out"]);
+    }
+
+    /// Merges a plan of `a.txt`, `b.txt` and `same.txt` into an output
+    /// directory where all of them exist and `same.txt` is unchanged.
+    fn merge(strategy: ConflictStrategy) -> (tempfile::TempDir, Result<BTreeMap<String, Vec<u8>>>) {
+        let parent = tempfile::tempdir().unwrap();
+        let out = parent.path().join("out");
+        std::fs::create_dir_all(&out).unwrap();
+        for (name, content) in [
+            ("a.txt", "old a"),
+            ("b.txt", "old b"),
+            ("same.txt", "same"),
+            ("keep.txt", "keep"),
+        ] {
+            std::fs::write(out.join(name), content).unwrap();
+        }
+
+        let plan = plan(&[
+            ("a.txt", Some("new a")),
+            ("b.txt", Some("new b")),
+            ("same.txt", Some("same")),
+            ("new.txt", Some("new")),
+        ]);
+        let result = plan.actions(&out, Some(&strategy)).and_then(|actions| {
+            assert_eq!(actions[2], Action::Skip);
+            assert_eq!(actions[3], Action::Create);
+            plan.commit(&actions, &out, true)
+        });
+        assert_eq!(file_names(parent.path()), vec!["out"]);
+        let tree = result.map(|_| tree(&out));
+        (parent, tree)
+    }
+
+    fn files(files: &[(&str, &str)]) -> BTreeMap<String, Vec<u8>> {
+        files.iter().map(|(name, content)| (name.to_string(), content.as_bytes().to_vec())).collect()
+    }
+
+    #[test]
+    fn merge_skips_conflicts() {
+        let (_dir, tree) = merge(ConflictStrategy::Skip);
+        assert_eq!(
+            tree.unwrap(),
+            files(&[
+                ("a.txt", "old a"),
+                ("b.txt", "old b"),
+                ("keep.txt", "keep"),
+                ("new.txt", "new"),
+                ("same.txt", "same")
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_overwrites_conflicts() {
+        let (_dir, tree) = merge(ConflictStrategy::Overwrite);
+        assert_eq!(
+            tree.unwrap(),
+            files(&[
+                ("a.txt", "new a"),
+                ("b.txt", "new b"),
+                ("keep.txt", "keep"),
+                ("new.txt", "new"),
+                ("same.txt", "same")
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_backs_up_conflicts() {
+        let (_dir, tree) = merge(ConflictStrategy::Backup);
+        assert_eq!(
+            tree.unwrap(),
+            files(&[
+                ("a.txt", "new a"),
+                ("a.txt.orig", "old a"),
+                ("b.txt", "new b"),
+                ("b.txt.orig", "old b"),
+                ("keep.txt", "keep"),
+                ("new.txt", "new"),
+                ("same.txt", "same"),
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_fails_on_conflicts_without_writing() {
+        let (dir, merged) = merge(ConflictStrategy::Fail);
+        let err = merged.unwrap_err().to_string();
+        assert_eq!(err, "unresolved conflict: a.txt");
+        assert_eq!(
+            tree(&dir.path().join("out")),
+            files(&[
+                ("a.txt", "old a"),
+                ("b.txt", "old b"),
+                ("keep.txt", "keep"),
+                ("same.txt", "same")
+            ])
+        );
+    }
+
+    #[test]
+    fn backups_never_overwrite_earlier_backups() {
+        let out = tempfile::tempdir().unwrap();
+        std::fs::write(out.path().join("a.txt"), "first").unwrap();
+        std::fs::write(out.path().join("a.txt.orig"), "backup").unwrap();
+        std::fs::write(out.path().join("a.txt.orig.1"), "backup 1").unwrap();
+
+        for content in ["second", "third"] {
+            let plan = plan(&[("a.txt", Some(content))]);
+            let actions = plan.actions(out.path(), Some(&ConflictStrategy::Backup)).unwrap();
+            assert_eq!(actions, vec![Action::Backup]);
+            plan.commit(&actions, out.path(), true).unwrap();
+        }
+        assert_eq!(
+            tree(out.path()),
+            files(&[
+                ("a.txt", "third"),
+                ("a.txt.orig", "backup"),
+                ("a.txt.orig.1", "backup 1"),
+                ("a.txt.orig.2", "first"),
+                ("a.txt.orig.3", "second"),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn when_of_an_invalid_variable_is_reported_with_all_invalid_values() {
+        let dir = template(
+            r#"
+version: "0.2"
+template:
+  variables:
+    docker:
+      type: bool
+      static: maybe
+    registry:
+      static: registry.example.com
+      when: "{{vars.docker}}"
+    port:
+      type: int
+      static: http
+"#,
+            &[],
+        );
+        let err = render_values(dir.path(), HashMap::new()).await.unwrap_err().to_string();
+        assert!(err.starts_with("invalid variable values:"), "{}", err);
+        assert_eq!(
+            err,
+            "invalid variable values:\ndocker: expected bool, got \"maybe\"\nport: expected int, got \"http\""
+        );
+    }
+
+    fn blueprint(yaml: &str) -> Blueprint {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn computed_variables_are_ordered_by_their_dependencies() {
+        let bp = blueprint(
+            r#"
+version: "0.2"
+template:
+  variables:
+    name:
+      static: demo
+  computed:
+    c: "{{vars.b}}-{{vars.case.upper}}"
+    b: "{{vars.a}}!"
+    a: "{{vars.name}}"
+    case.upper: "{{upper_case vars.name}}"
+    d: "{{vars.name}}"
+"#,
+        );
+        let order = computed_order(&bp).unwrap().into_iter().map(|n| n.as_str()).collect::<Vec<_>>();
+        let position = |name| order.iter().position(|n| *n == name).unwrap();
+        assert_eq!(order.len(), 5);
+        assert!(position("a") < position("b"));
+        assert!(position("b") < position("c"));
+        assert!(position("case.upper") < position("c"));
+    }
+
+    #[test]
+    fn cyclic_computed_variables_are_rejected() {
+        let bp = blueprint(
+            r#"
+version: "0.2"
+template:
+  computed:
+    a: "{{vars.b}}"
+    b: "{{vars.c}}"
+    c: "{{vars.a}}"
+"#,
+        );
+        assert_eq!(
+            computed_order(&bp).unwrap_err().to_string(),
+            "cyclic computed variables: a -> b -> c -> a"
+        );
+    }
+
+    #[tokio::test]
+    async fn nested_variables_are_rendered_independent_of_their_order() {
+        let dir = template(
+            r#"
+version: "0.2"
+template:
+  variables:
+    app.port:
+      type: int
+      static: "8080"
+    app.name:
+      static: demo
+    docker:
+      type: bool
+      static: "false"
+"#,
+            &[(
+                "out.txt",
+                "{{vars.app.name}}:{{vars.app.port}} {{#if vars.docker}}docker{{else}}plain{{/if}}",
+            )],
+        );
+        for _ in 0..16 {
+            let plan = render_values(dir.path(), HashMap::new()).await.unwrap();
+            let Content::File(data) = &plan.entries[0].content else {
+                panic!("expected a file");
+            };
+            assert_eq!(String::from_utf8_lossy(data), "demo:8080 plain");
+        }
+    }
+
+    #[tokio::test]
+    async fn overlapping_variable_names_are_rejected() {
+        let dir = template(
+            r#"
+version: "0.2"
+template:
+  variables:
+    docker:
+      type: bool
+      static: "true"
+    docker.registry:
+      static: registry.example.com
+"#,
+            &[],
+        );
+        let err = render_values(dir.path(), HashMap::new()).await.unwrap_err().to_string();
+        assert!(
+            err.contains("variable \"docker\" overlaps with \"docker.registry\""),
+            "{}",
+            err
+        );
+
+        // undeclared values are checked when the context is built, in either order
+        let dir = template(BLUEPRINT, &[]);
+        for _ in 0..16 {
+            let values = HashMap::from([
+                ("name".to_owned(), "x".to_owned()),
+                ("extra".to_owned(), "x".to_owned()),
+                ("extra.nested".to_owned(), "x".to_owned()),
+            ]);
+            let err = render_values(dir.path(), values).await.unwrap_err().to_string();
+            assert_eq!(
+                err,
+                "variable extra.nested overlaps with another variable, a variable can not have nested variables"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn when_may_reference_skipped_variables() {
+        let dir = template(
+            r#"
+version: "0.2"
+template:
+  variables:
+    feature.docker:
+      type: bool
+      static: "false"
+    registry:
+      static: registry.example.com
+      when: "{{vars.feature.docker}}"
+    port:
+      type: int
+      static: "5000"
+      when: "{{vars.registry}}"
+    fallback:
+      static: plain
+      when: "{{#unless vars.registry}}true{{/unless}}"
+"#,
+            &[],
+        );
+        let plan = render_values(dir.path(), HashMap::new()).await.unwrap();
+        let mut names = plan.values.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["fallback", "feature.docker"]);
+
+        let values = HashMap::from([("feature.docker".to_owned(), "true".to_owned())]);
+        let plan = render_values(dir.path(), values).await.unwrap();
+        let mut names = plan.values.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["feature.docker", "port", "registry"]);
+    }
+
+    #[tokio::test]
+    async fn rules_may_reference_skipped_variables() {
+        let dir = template(
+            r#"
+version: "0.2"
+template:
+  variables:
+    docker:
+      type: bool
+      static: "false"
+    registry:
+      static: registry.example.com
+      when: "{{vars.docker}}"
+  rules:
+    "reg/**": "{{vars.registry}}"
+"#,
+            &[("reg/file.txt", "{{vars.registry}}"), ("always.txt", "x")],
+        );
+        let paths = |plan: Plan| plan.entries.into_iter().map(|e| e.path.display().to_string()).collect::<Vec<_>>();
+        assert_eq!(paths(render_values(dir.path(), HashMap::new()).await.unwrap()), [
+            "always.txt"
+        ]);
+
+        let values = HashMap::from([("docker".to_owned(), "true".to_owned())]);
+        assert_eq!(paths(render_values(dir.path(), values).await.unwrap()), [
+            "always.txt",
+            "reg",
+            "reg/file.txt"
+        ]);
+    }
+
+    fn references(source: &str) -> Vec<(String, (usize, usize))> {
+        let template = handlebars::Template::compile(source).unwrap();
+        let mut references = Vec::new();
+        template_references(
+            &template,
+            &regex::Regex::new(VARIABLE_REFERENCE).unwrap(),
+            &mut references,
+        );
+        references
+    }
+
+    #[test]
+    fn only_expressions_reference_variables() {
+        assert_eq!(references("console.log(vars.debug);\n{{! vars.comment }}"), vec![]);
+        assert_eq!(
+            references("name: {{vars.app.name}}\n{{#if vars.docker}}\n  {{snake_case (trim vars.title)}}\n{{/if}}"),
+            vec![
+                ("app.name".to_owned(), (1, 7)),
+                ("docker".to_owned(), (2, 1)),
+                ("title".to_owned(), (3, 3)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn every_undefined_variable_of_a_file_is_reported() {
+        let dir = template(BLUEPRINT, &[
+            (
+                "a.txt",
+                "{{vars.nmae}}\n{{vars.name}} {{vars.oops}}\n{{#each vars.items}}{{this}}{{/each}}",
+            ),
+            ("b.txt", "{{#if vars.flag}}{{/if}}{{vars.missing}}"),
+        ]);
+        let err = render_with(dir.path(), "x").await.unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "failed to render the template:\na.txt:1:1: undefined variable \"vars.nmae\"\na.txt:2:15: undefined \
+             variable \"vars.oops\"\na.txt:3:1: undefined variable \"vars.items\"\nb.txt:1:25: undefined variable \
+             \"vars.missing\""
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use std::path::Path;
+
+use anyhow::Result;
+use git2::{
+    CredentialType,
+    FetchOptions,
+    RemoteCallbacks,
+};
+
+use crate::{
+    args::GitOptions,
+    cache,
+};
+
+const DEFAULT_BRANCH_REF: &str = "refs/remotes/origin/HEAD";
+/// Fetch depth that converts a shallow clone into a complete one.
+const UNSHALLOW: i32 = i32::MAX;
+const TOKEN_ENV: &str = "RANGER_GIT_TOKEN";
+const PASSPHRASE_ENV: &str = "RANGER_SSH_PASSPHRASE";
+const ASKPASS_ENV: &str = "GIT_ASKPASS";
+
+/// Checks out `reference` of `repo` into `target`. The reference may be a
+/// branch, a tag or a full or abbreviated commit SHA, the default branch of
+/// the remote is used if none is given. The repository is fetched into the
+/// cache first, or only looked up there when offline. Returns the SHA of the
+/// checked out commit.
+pub fn clone_git(repo: &str, reference: Option<&str>, options: &GitOptions, target: &Path) -> Result<String> {
+    clone_cached(&cache::dir()?, repo, reference, options, target)
+}
+
+/// Like [`clone_git`] with the cache at `cache_dir`. A cached clone that is
+/// created but can not be fetched into is removed again.
+fn clone_cached(
+    cache_dir: &Path,
+    repo: &str,
+    reference: Option<&str>,
+    options: &GitOptions,
+    target: &Path,
+) -> Result<String> {
+    let (repository, created) = cache::open(cache_dir, repo)?;
+    let object = if options.offline {
+        resolve_cached(&repository, reference).ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} is not cached for {} - run once without --offline",
+                reference.unwrap_or("the default branch"),
+                repo
+            )
+        })
+    } else {
+        fetch(&repository, repo, reference, options)
+    };
+    let object = match object {
+        | Ok(object) => object,
+        | Err(e) => {
+            if created {
+                let _ = std::fs::remove_dir_all(repository.path());
+            }
+            return Err(e);
+        },
+    };
+
+    let commit = object.peel_to_commit()?;
+    std::fs::create_dir_all(target)?;
+    repository.checkout_tree(
+        commit.as_object(),
+        Some(git2::build::CheckoutBuilder::new().force().target_dir(target)),
+    )?;
+    Ok(commit.id().to_string())
+}
+
+/// Fetches `reference` from the remote into the cached `repository`.
+fn fetch<'r>(
+    repository: &'r git2::Repository,
+    repo: &str,
+    reference: Option<&str>,
+    options: &GitOptions,
+) -> Result<git2::Object<'r>> {
+    let mut remote = repository.find_remote("origin")?;
+    let (heads, default_branch) = {
+        let connection = remote.connect_auth(git2::Direction::Fetch, Some(callbacks(options)), None)?;
+        let heads = connection.list()?.iter().map(|h| (h.name().to_owned(), h.oid())).collect::<Vec<_>>();
+        let default_branch = connection.default_branch().ok().and_then(|b| b.as_str().map(str::to_owned));
+        (heads, default_branch)
+    };
+
+    let head = match reference {
+        | None => {
+            let name =
+                default_branch.ok_or_else(|| anyhow::anyhow!("could not determine the default branch of {}", repo))?;
+            repository.reference_symbolic(DEFAULT_BRANCH_REF, &tracking_ref(&name), true, "remote HEAD")?;
+            heads.iter().find(|(n, _)| n == &name)
+        },
+        | Some(reference) => {
+            [
+                format!("refs/heads/{}", reference),
+                format!("refs/tags/{}", reference),
+                reference.to_owned(),
+            ]
+            .iter()
+            .find_map(|name| heads.iter().find(|(n, _)| n == name))
+        },
+    };
+
+    match (head, reference) {
+        | (Some((name, oid)), _) => {
+            // only fetch what is not cached already
+            if repository.find_object(*oid, None).is_err() {
+                remote.fetch(&[name], Some(&mut fetch_options(repo, options, 1)), None)?;
+            }
+            repository.reference(&tracking_ref(name), *oid, true, "fetch")?;
+            Ok(repository.find_object(*oid, None)?)
+        },
+        | (None, Some(reference)) => {
+            if let Ok(object) = repository.revparse_single(reference) {
+                return Ok(object);
+            }
+            // not a named ref, try to fetch the commit directly if the server allows it
+            // and fall back to fetching the complete history of all branches and tags
+            let fetched = is_full_sha(reference)
+                && remote.fetch(&[reference], Some(&mut fetch_options(repo, options, 1)), None).is_ok();
+            if !fetched {
+                remote.fetch(
+                    &["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"],
+                    Some(&mut fetch_options(repo, options, UNSHALLOW)),
+                    None,
+                )?;
+            }
+            repository
+                .revparse_single(reference)
+                .map_err(|_| anyhow::anyhow!("ref {} not found in {}", reference, repo))
+        },
+        | (None, None) => Err(anyhow::anyhow!("could not determine the default branch of {}", repo)),
+    }
+}
+
+/// Looks up `reference` in the cached `repository` without contacting the
+/// remote.
+fn resolve_cached<'r>(repository: &'r git2::Repository, reference: Option<&str>) -> Option<git2::Object<'r>> {
+    let Some(reference) = reference else {
+        return repository.revparse_single(DEFAULT_BRANCH_REF).ok();
+    };
+    [
+        format!("refs/remotes/origin/{}", reference),
+        format!("refs/tags/{}", reference),
+        reference.to_owned(),
+    ]
+    .iter()
+    .find_map(|name| repository.revparse_single(name).ok())
+}
+
+/// The ref a remote ref is stored as in the cache.
+fn tracking_ref(name: &str) -> String {
+    match name.strip_prefix("refs/heads/") {
+        | Some(branch) => format!("refs/remotes/origin/{}", branch),
+        | None => name.to_owned(),
+    }
+}
+
+fn fetch_options<'a>(repo: &str, options: &'a GitOptions, depth: i32) -> FetchOptions<'a> {
+    let mut fo = FetchOptions::new();
+    fo.remote_callbacks(callbacks(options));
+    // the local transport does not support shallow fetches
+    if !is_local(repo) {
+        fo.depth(depth);
+    }
+    fo
+}
+
+/// A way of authenticating against a remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    SshKey,
+    SshAgent,
+    Token,
+    CredentialHelper,
+    Askpass,
+    Default,
+}
+
+impl Method {
+    fn credential_type(&self) -> CredentialType {
+        match self {
+            | Method::SshKey | Method::SshAgent => CredentialType::SSH_KEY,
+            | Method::Token | Method::CredentialHelper | Method::Askpass => CredentialType::USER_PASS_PLAINTEXT,
+            | Method::Default => CredentialType::DEFAULT,
+        }
+    }
+}
+
+/// The configured authentication methods in the order they are tried: the
+/// explicit SSH key or the ssh-agent for SSH remotes and the token from
+/// `RANGER_GIT_TOKEN`, the git credential helpers and `GIT_ASKPASS` for HTTPS
+/// remotes.
+fn methods(options: &GitOptions, is_set: impl Fn(&str) -> bool) -> Vec<Method> {
+    let mut methods = vec![if options.ssh_key.is_some() {
+        Method::SshKey
+    } else {
+        Method::SshAgent
+    }];
+    if is_set(TOKEN_ENV) {
+        methods.push(Method::Token);
+    }
+    methods.push(Method::CredentialHelper);
+    if is_set(ASKPASS_ENV) {
+        methods.push(Method::Askpass);
+    }
+    methods.push(Method::Default);
+    methods
+}
+
+/// The first of `methods` that the remote accepts and that was not tried yet.
+fn next_method(methods: &[Method], allowed: CredentialType, tried: &[Method]) -> Option<Method> {
+    methods.iter().copied().find(|m| allowed.contains(m.credential_type()) && !tried.contains(m))
+}
+
+/// Credential callbacks trying every configured method (see `methods`) once.
+fn callbacks(options: &GitOptions) -> RemoteCallbacks<'_> {
+    let methods = methods(options, |name| std::env::var_os(name).is_some());
+    let mut tried = Vec::new();
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        if allowed.contains(CredentialType::USERNAME) {
+            return git2::Cred::username(username.unwrap_or("git"));
+        }
+        while let Some(method) = next_method(&methods, allowed, &tried) {
+            tried.push(method);
+            return match method {
+                | Method::SshKey => {
+                    let key = options.ssh_key.as_deref().unwrap_or_default();
+                    let passphrase = std::env::var(PASSPHRASE_ENV).ok();
+                    git2::Cred::ssh_key(username.unwrap_or("git"), None, Path::new(key), passphrase.as_deref())
+                },
+                | Method::SshAgent => git2::Cred::ssh_key_from_agent(username.unwrap_or("git")),
+                | Method::Token => {
+                    let token = std::env::var(TOKEN_ENV).unwrap_or_default();
+                    git2::Cred::userpass_plaintext(username.unwrap_or("x-access-token"), &token)
+                },
+                | Method::CredentialHelper => {
+                    match git2::Config::open_default().and_then(|c| git2::Cred::credential_helper(&c, url, username)) {
+                        | Ok(cred) => Ok(cred),
+                        | Err(_) => continue, // no helper or no stored credentials
+                    }
+                },
+                | Method::Askpass => {
+                    askpass_credentials(&std::env::var(ASKPASS_ENV).unwrap_or_default(), url, username)
+                },
+                | Method::Default => git2::Cred::default(),
+            };
+        }
+        Err(git2::Error::from_str(&format!("authentication failed for {}", url)))
+    });
+    callbacks
+}
+
+fn askpass_credentials(askpass: &str, url: &str, username: Option<&str>) -> Result<git2::Cred, git2::Error> {
+    let ask = |prompt: String| -> Result<String, git2::Error> {
+        let output = std::process::Command::new(askpass)
+            .arg(prompt)
+            .output()
+            .map_err(|e| git2::Error::from_str(&format!("failed to run {}: {}", askpass, e)))?;
+        if !output.status.success() {
+            return Err(git2::Error::from_str(&format!(
+                "{} exited with {}",
+                askpass, output.status
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim_end_matches(['\r', '\n']).to_owned())
+    };
+    let username = match username {
+        | Some(username) => username.to_owned(),
+        | None => ask(format!("Username for '{}': ", url))?,
+    };
+    let password = ask(format!("Password for '{}': ", url))?;
+    git2::Cred::userpass_plaintext(&username, &password)
+}
+
+fn is_full_sha(reference: &str) -> bool {
+    reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_local(repo: &str) -> bool {
+    repo.starts_with("file://") || Path::new(repo).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(ssh_key: Option<&str>, offline: bool) -> GitOptions {
+        GitOptions {
+            ssh_key: ssh_key.map(str::to_owned),
+            offline,
+        }
+    }
+
+    fn commit(repository: &git2::Repository, branch: &str, content: &str) -> git2::Oid {
+        let refname = format!("refs/heads/{}", branch);
+        let parent = repository.find_reference(&refname).and_then(|r| r.peel_to_commit()).ok();
+        std::fs::write(repository.workdir().unwrap().join("file.txt"), content).unwrap();
+        let mut index = repository.index().unwrap();
+        index.add_path(Path::new("file.txt")).unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        repository
+            .commit(
+                Some(&refname),
+                &signature,
+                &signature,
+                content,
+                &tree,
+                &parent.iter().collect::<Vec<_>>(),
+            )
+            .unwrap()
+    }
+
+    fn checkout(cache: &Path, repo: &str, reference: Option<&str>, offline: bool) -> Result<(String, String)> {
+        let target = tempfile::tempdir().unwrap();
+        let commit = clone_cached(cache, repo, reference, &options(None, offline), target.path())?;
+        Ok((commit, std::fs::read_to_string(target.path().join("file.txt"))?))
+    }
+
+    #[test]
+    fn clones_file_remotes() {
+        let cache = tempfile::tempdir().unwrap();
+
+        let remote = tempfile::tempdir().unwrap();
+        let repository = git2::Repository::init(remote.path()).unwrap();
+        repository.set_head("refs/heads/main").unwrap();
+        let first = commit(&repository, "main", "first");
+        repository.tag_lightweight("v1", &repository.find_object(first, None).unwrap(), false).unwrap();
+        let second = commit(&repository, "main", "second");
+        repository.branch("feature", &repository.find_commit(second).unwrap(), false).unwrap();
+        let feature = commit(&repository, "feature", "feature");
+        let repo = format!("file://{}", remote.path().display());
+
+        // nothing is cached yet, failed clones are not kept in the cache
+        assert!(checkout(cache.path(), &repo, None, true).is_err());
+        assert!(checkout(cache.path(), "file:///does/not/exist", None, false).is_err());
+        assert_eq!(std::fs::read_dir(cache.path()).unwrap().count(), 0);
+
+        assert_eq!(
+            checkout(cache.path(), &repo, None, false).unwrap(),
+            (second.to_string(), "second".to_owned())
+        );
+        assert_eq!(
+            checkout(cache.path(), &repo, Some("feature"), false).unwrap(),
+            (feature.to_string(), "feature".to_owned())
+        );
+        assert_eq!(
+            checkout(cache.path(), &repo, Some("v1"), false).unwrap(),
+            (first.to_string(), "first".to_owned())
+        );
+        assert_eq!(
+            checkout(cache.path(), &repo, Some(&first.to_string()[..7]), false).unwrap(),
+            (first.to_string(), "first".to_owned())
+        );
+        assert!(checkout(cache.path(), &repo, Some("missing"), false).is_err());
+
+        // offline, everything is resolved from the cache even if the remote is gone
+        drop(remote);
+        assert_eq!(
+            checkout(cache.path(), &repo, None, true).unwrap(),
+            (second.to_string(), "second".to_owned())
+        );
+        assert_eq!(
+            checkout(cache.path(), &repo, Some("feature"), true).unwrap(),
+            (feature.to_string(), "feature".to_owned())
+        );
+        assert_eq!(
+            checkout(cache.path(), &repo, Some("v1"), true).unwrap(),
+            (first.to_string(), "first".to_owned())
+        );
+        assert_eq!(
+            checkout(cache.path(), &repo, Some(&first.to_string()[..7]), true).unwrap(),
+            (first.to_string(), "first".to_owned())
+        );
+        assert!(checkout(cache.path(), &repo, Some("missing"), true).is_err());
+    }
+
+    #[test]
+    fn credential_methods_are_tried_in_order() {
+        let none = |_: &str| false;
+        let all = |_: &str| true;
+        assert_eq!(methods(&options(None, false), none), vec![
+            Method::SshAgent,
+            Method::CredentialHelper,
+            Method::Default
+        ]);
+        assert_eq!(methods(&options(Some("id_ed25519"), false), all), vec![
+            Method::SshKey,
+            Method::Token,
+            Method::CredentialHelper,
+            Method::Askpass,
+            Method::Default
+        ]);
+    }
+
+    #[test]
+    fn every_credential_method_is_tried_once() {
+        let methods = methods(&options(None, false), |_| true);
+        let mut tried = Vec::new();
+        let mut next = |allowed| {
+            let method = next_method(&methods, allowed, &tried);
+            tried.extend(method);
+            method
+        };
+
+        assert_eq!(next(CredentialType::SSH_KEY), Some(Method::SshAgent));
+        assert_eq!(next(CredentialType::SSH_KEY), None);
+        assert_eq!(next(CredentialType::USER_PASS_PLAINTEXT), Some(Method::Token));
+        assert_eq!(
+            next(CredentialType::USER_PASS_PLAINTEXT),
+            Some(Method::CredentialHelper)
+        );
+        assert_eq!(next(CredentialType::USER_PASS_PLAINTEXT), Some(Method::Askpass));
+        assert_eq!(next(CredentialType::USER_PASS_PLAINTEXT), None);
+        assert_eq!(
+            next(CredentialType::USER_PASS_PLAINTEXT | CredentialType::DEFAULT),
+            Some(Method::Default)
+        );
+        assert_eq!(next(CredentialType::all()), None);
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use complate::{
+    config::{
+        OptionValue,
+        VariableDefinition,
+    },
+    render::ShellTrust,
+};
+
+use crate::{
+    args::Trust,
+    blueprint::Blueprint,
+    config::Config,
+};
+
+/// Lists every shell command the blueprint may execute, either as a variable
+/// value, a selectable option or a helper.
+pub fn shell_commands(bp: &Blueprint) -> Vec<String> {
+    let mut commands = Vec::new();
+    for (name, variable) in bp.template.variables.iter().flatten() {
+        match &variable.definition {
+            | VariableDefinition::Shell(cmd) => commands.push(format!("variable {}: {}", name, cmd)),
+            | VariableDefinition::Select { options, .. } | VariableDefinition::Check { options, .. } => {
+                for (key, option) in options {
+                    if let OptionValue::Shell(cmd) = &option.value {
+                        commands.push(format!("variable {} (option {}): {}", name, key, cmd));
+                    }
+                }
+            },
+            | _ => {},
+        }
+    }
+    let mut helpers = bp.template.helpers.iter().flatten().collect::<Vec<_>>();
+    helpers.sort();
+    for (name, cmd) in helpers {
+        commands.push(format!("helper {}: {}", name, cmd));
+    }
+    commands
+}
+
+/// Determines whether the blueprints from `source` may execute shell commands.
+/// With `Trust::Prompt`, sources that are not in the persisted trust list have
+/// to be confirmed by the user after all commands have been listed.
+pub async fn resolve(
+    trust: &Trust,
+    source: &str,
+    blueprints: &[&Blueprint],
+    backend: &complate::render::Backend,
+) -> Result<ShellTrust> {
+    match trust {
+        | Trust::None => return Ok(ShellTrust::None),
+        | Trust::Ultimate => return Ok(ShellTrust::Ultimate),
+        | Trust::Prompt => {},
+    }
+
+    let commands = blueprints.iter().flat_map(|bp| shell_commands(bp)).collect::<Vec<_>>();
+    if commands.is_empty() {
+        return Ok(ShellTrust::None);
+    }
+    let mut config = Config::load()?;
+    if config.trusted.iter().any(|t| t == source) {
+        return Ok(ShellTrust::Ultimate);
+    }
+
+    eprintln!("the template from {} executes the following shell commands:", source);
+    for command in &commands {
+        eprintln!("  {}", command);
+    }
+    if !matches!(backend, complate::render::Backend::CLI) {
+        return Err(anyhow::anyhow!(
+            "refusing to execute shell commands from an untrusted template - run with --interactive to confirm or \
+             pass --trust ultimate"
+        ));
+    }
+
+    let mut options = BTreeMap::new();
+    for (key, display) in [("0", "no"), ("1", "yes, once"), ("2", "yes, always trust this source")] {
+        options.insert(key.to_owned(), complate::config::Option {
+            display: display.to_owned(),
+            value: OptionValue::Static(key.to_owned()),
+        });
+    }
+    let input = backend.to_input(&ShellTrust::None)?;
+    match input.select("execute these commands?", &options).await?.as_str() {
+        | "1" => Ok(ShellTrust::Ultimate),
+        | "2" => {
+            config.trusted.push(source.to_owned());
+            config.save()?;
+            Ok(ShellTrust::Ultimate)
+        },
+        | _ => Err(anyhow::anyhow!("shell commands were not trusted")),
+    }
+}
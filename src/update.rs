@@ -0,0 +1,443 @@
+use std::{
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::Result;
+
+use crate::{
+    answers::{
+        self,
+        Answers,
+    },
+    args::{
+        GitOptions,
+        UpdateOptions,
+    },
+    blueprint::Blueprint,
+    render::{
+        self,
+        Content,
+    },
+    source,
+    trust,
+};
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Change {
+    Create,
+    Update,
+    Merge,
+    Conflict,
+    Delete,
+    Keep,
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            | Change::Create => "create",
+            | Change::Update => "update",
+            | Change::Merge => "merge",
+            | Change::Conflict => "conflict",
+            | Change::Delete => "delete",
+            | Change::Keep => "keep",
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Update {
+    path: PathBuf,
+    change: Change,
+    content: Option<Content>,
+}
+
+pub async fn update(options: &UpdateOptions, git: &GitOptions, backend: &complate::render::Backend) -> Result<()> {
+    let out_path_root = Path::new(&options.out);
+    let answers = Answers::load(out_path_root)?;
+    let answers::Source::Git {
+        repo,
+        reference: recorded_reference,
+        folder,
+        commit,
+    } = &answers.source
+    else {
+        return Err(anyhow::anyhow!(
+            "update is only supported for folders generated from a git repository"
+        ));
+    };
+    let reference = options.reference.to_owned().or(recorded_reference.to_owned());
+
+    let temp_dir = Path::join(&std::env::temp_dir(), uuid::Uuid::new_v4().to_string());
+    let base_dir = Path::join(&temp_dir, "base");
+    let new_dir = Path::join(&temp_dir, "new");
+    let updates = async {
+        source::clone_git(repo, Some(commit), git, &base_dir)?;
+        let new_commit = source::clone_git(repo, reference.as_deref(), git, &new_dir)?;
+
+        let new_root = Path::join(&new_dir, folder);
+        let new_blueprint = Blueprint::load(&new_root)?;
+        let vars = options.vars.with_env(&new_blueprint);
+        let mut values = answers.variables.clone().into_iter().collect::<HashMap<_, _>>();
+        values.extend(vars.values.iter().map(|(k, v)| (k.to_owned(), v.to_owned())));
+        let base_root = Path::join(&base_dir, folder);
+        let base_blueprint = Blueprint::load(&base_root)?;
+        let shell_trust = trust::resolve(&options.trust, repo, &[&base_blueprint, &new_blueprint], backend).await?;
+        let new_plan = render::render(
+            &new_blueprint,
+            &values,
+            &new_root,
+            backend,
+            &shell_trust,
+            options.strict,
+        )
+        .await?;
+        if options.verbose {
+            let mut origins =
+                answers.variables.keys().map(|k| (k.to_owned(), "answers".to_owned())).collect::<HashMap<_, _>>();
+            origins.extend(vars.origins.iter().map(|(k, v)| (k.to_owned(), v.to_owned())));
+            new_plan.print_values(&origins);
+        }
+
+        // render the base with the recorded values, falling back to the current ones
+        // for everything that was not recorded
+        let mut values = new_plan.values.clone();
+        values.extend(answers.variables.iter().map(|(k, v)| (k.to_owned(), v.to_owned())));
+        let base_plan = render::render(
+            &base_blueprint,
+            &values,
+            &base_root,
+            backend,
+            &shell_trust,
+            options.strict,
+        )
+        .await?;
+
+        let mut updates = diff(out_path_root, &base_plan, &new_plan)?;
+        let answers = Answers::new(
+            answers::Source::Git {
+                repo: repo.to_owned(),
+                reference,
+                folder: folder.to_owned(),
+                commit: new_commit,
+            },
+            &new_blueprint,
+            &new_plan.values,
+        );
+        let answers = answers.to_yaml()?.into_bytes();
+        if std::fs::read(Path::join(out_path_root, answers::FILE_NAME)).ok() != Some(answers.clone()) {
+            updates.push(Update {
+                path: PathBuf::from(answers::FILE_NAME),
+                change: Change::Update,
+                content: Some(Content::File(answers)),
+            });
+        }
+        Ok::<_, anyhow::Error>(updates)
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(&temp_dir); // remove temp dir in any case
+    let updates = updates?;
+
+    for update in &updates {
+        println!("{:<9} {}", update.change, update.path.display());
+    }
+    if options.dry_run {
+        return Ok(());
+    }
+
+    apply(out_path_root, &updates)?;
+
+    let conflicts = updates.iter().filter(|u| u.change == Change::Conflict).count();
+    if conflicts > 0 {
+        eprintln!(
+            "{} file(s) contain conflict markers that need to be resolved manually",
+            conflicts
+        );
+    }
+    Ok(())
+}
+
+/// Applies the updates to the output directory. New file contents are written
+/// into a staging directory first and only moved into place once all of them
+/// were written, so a failure leaves the output directory untouched.
+fn apply(out_path_root: &Path, updates: &[Update]) -> Result<()> {
+    let out_path_root = render::absolute(out_path_root)?;
+    let staging = render::sibling(&out_path_root, "staging")?;
+    let result = stage(&out_path_root, &staging, updates).and_then(|_| {
+        for update in updates {
+            let out_path = Path::join(&out_path_root, &update.path);
+            match (&update.change, &update.content) {
+                | (Change::Delete, _) => {
+                    std::fs::remove_file(&out_path)?;
+                    prune(&out_path_root, &out_path)?;
+                },
+                | (_, Some(Content::Directory)) => std::fs::create_dir_all(out_path)?,
+                | (_, Some(Content::File(_))) => {
+                    if let Some(parent) = out_path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::rename(Path::join(&staging, &update.path), out_path)?;
+                },
+                | (_, None) => {},
+            }
+        }
+        Ok(())
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
+fn stage(out_path_root: &Path, staging: &Path, updates: &[Update]) -> Result<()> {
+    for update in updates {
+        render::ensure_contained(out_path_root, &update.path)?;
+        if let Some(Content::File(data)) = &update.content {
+            let staged_path = Path::join(staging, &update.path);
+            if let Some(parent) = staged_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(staged_path, data)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the directories above `path` that are empty now, up to the output
+/// directory.
+fn prune(out_path_root: &Path, path: &Path) -> Result<()> {
+    for dir in path.ancestors().skip(1).take_while(|dir| *dir != out_path_root) {
+        if std::fs::read_dir(dir)?.next().is_some() {
+            break;
+        }
+        std::fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+fn diff(out_path_root: &Path, base: &render::Plan, new: &render::Plan) -> Result<Vec<Update>> {
+    let base_files = base
+        .entries
+        .iter()
+        .filter_map(|e| {
+            match &e.content {
+                | Content::File(data) => Some((&e.path, data)),
+                | Content::Directory => None,
+            }
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut updates = Vec::new();
+    for entry in &new.entries {
+        render::ensure_contained(out_path_root, &entry.path)?;
+        let out_path = Path::join(out_path_root, &entry.path);
+        let theirs = match &entry.content {
+            | Content::Directory => {
+                if !out_path.is_dir() {
+                    updates.push(Update {
+                        path: entry.path.clone(),
+                        change: Change::Create,
+                        content: Some(Content::Directory),
+                    });
+                }
+                continue;
+            },
+            | Content::File(data) => data,
+        };
+
+        let base = base_files.get(&entry.path);
+        let (change, data) = match (base, std::fs::read(&out_path).ok()) {
+            // deleted locally
+            | (Some(_), None) => (Change::Keep, None),
+            | (None, None) => (Change::Create, Some(theirs.to_owned())),
+            | (_, Some(ours)) if &ours == theirs => continue,
+            | (Some(base), Some(ours)) if &ours == *base => (Change::Update, Some(theirs.to_owned())),
+            | (Some(base), Some(_)) if theirs == *base => continue,
+            // binary files can not be merged, local changes win
+            | (_, Some(ours)) if render::is_binary(&ours) || render::is_binary(theirs) => (Change::Keep, None),
+            | (base, Some(ours)) => {
+                match diffy::merge_bytes(base.map(|b| b.as_slice()).unwrap_or_default(), &ours, theirs) {
+                    | Ok(merged) => (Change::Merge, Some(merged)),
+                    | Err(conflicted) => (Change::Conflict, Some(conflicted)),
+                }
+            },
+        };
+        updates.push(Update {
+            path: entry.path.clone(),
+            change,
+            content: data.map(Content::File),
+        });
+    }
+
+    let new_paths = new.entries.iter().map(|e| &e.path).collect::<std::collections::HashSet<_>>();
+    for (path, base) in base_files {
+        if new_paths.contains(path) {
+            continue;
+        }
+        let change = match std::fs::read(Path::join(out_path_root, path)) {
+            | Ok(ours) if &ours == base => Change::Delete,
+            | Ok(_) => Change::Keep,
+            | Err(_) => continue,
+        };
+        updates.push(Update {
+            path: path.clone(),
+            change,
+            content: None,
+        });
+    }
+
+    Ok(updates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(files: &[(&str, &str)]) -> render::Plan {
+        render::Plan {
+            entries: files
+                .iter()
+                .map(|(path, content)| {
+                    render::Entry {
+                        path: PathBuf::from(path),
+                        content: Content::File(content.as_bytes().to_vec()),
+                    }
+                })
+                .collect(),
+            values: HashMap::new(),
+            origins: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn diff_classifies_changes() {
+        let out = tempfile::tempdir().unwrap();
+        for (path, content) in [
+            ("unchanged", "same\n"),
+            ("update", "old\n"),
+            ("merge", "ours\nb\nc\n"),
+            ("conflict", "ours\n"),
+            ("delete", "removed\n"),
+            ("keep", "changed locally\n"),
+        ] {
+            std::fs::write(out.path().join(path), content).unwrap();
+        }
+
+        let base = plan(&[
+            ("unchanged", "same\n"),
+            ("update", "old\n"),
+            ("merge", "a\nb\nc\n"),
+            ("conflict", "base\n"),
+            ("delete", "removed\n"),
+            ("keep", "removed\n"),
+            ("deleted locally", "x\n"),
+        ]);
+        let new = plan(&[
+            ("unchanged", "same\n"),
+            ("create", "created\n"),
+            ("update", "new\n"),
+            ("merge", "a\nb\ntheirs\n"),
+            ("conflict", "theirs\n"),
+            ("deleted locally", "y\n"),
+        ]);
+
+        let mut updates = diff(out.path(), &base, &new)
+            .unwrap()
+            .into_iter()
+            .map(|u| {
+                let content = match u.content {
+                    | Some(Content::File(data)) => Some(String::from_utf8(data).unwrap()),
+                    | _ => None,
+                };
+                (u.path.display().to_string(), u.change, content)
+            })
+            .collect::<Vec<_>>();
+        updates.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(updates.len(), 7, "{:?}", updates);
+        assert_eq!(
+            updates[0],
+            ("conflict".to_owned(), Change::Conflict, updates[0].2.clone())
+        );
+        assert!(updates[0].2.as_deref().unwrap().contains("<<<<<<<"));
+        assert_eq!(updates[1..], [
+            ("create".to_owned(), Change::Create, Some("created\n".to_owned())),
+            ("delete".to_owned(), Change::Delete, None),
+            ("deleted locally".to_owned(), Change::Keep, None),
+            ("keep".to_owned(), Change::Keep, None),
+            ("merge".to_owned(), Change::Merge, Some("ours\nb\ntheirs\n".to_owned())),
+            ("update".to_owned(), Change::Update, Some("new\n".to_owned())),
+        ]);
+    }
+
+    fn update(path: &str, change: Change, content: Option<&str>) -> Update {
+        Update {
+            path: PathBuf::from(path),
+            change,
+            content: content.map(|c| Content::File(c.as_bytes().to_vec())),
+        }
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names = walkdir::WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .map(|e| e.unwrap().path().strip_prefix(dir).unwrap().display().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn apply_writes_updates_and_prunes_empty_directories() {
+        let parent = tempfile::tempdir().unwrap();
+        let out = parent.path().join("out");
+        std::fs::create_dir_all(out.join("gone/sub")).unwrap();
+        std::fs::create_dir_all(out.join("kept")).unwrap();
+        std::fs::write(out.join("gone/sub/file"), "removed\n").unwrap();
+        std::fs::write(out.join("kept/file"), "removed\n").unwrap();
+        std::fs::write(out.join("kept/other"), "other\n").unwrap();
+        std::fs::write(out.join("update"), "old\n").unwrap();
+
+        apply(&out, &[
+            update("gone/sub/file", Change::Delete, None),
+            update("kept/file", Change::Delete, None),
+            update("update", Change::Update, Some("new\n")),
+            update("new/file", Change::Create, Some("created\n")),
+            update("keep", Change::Keep, None),
+        ])
+        .unwrap();
+        assert_eq!(file_names(&out), vec![
+            "kept",
+            "kept/other",
+            "new",
+            "new/file",
+            "update"
+        ]);
+        assert_eq!(std::fs::read_to_string(out.join("update")).unwrap(), "new\n");
+        assert_eq!(std::fs::read_to_string(out.join("new/file")).unwrap(), "created\n");
+        assert_eq!(std::fs::read_dir(parent.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failed_apply_leaves_the_output_directory_untouched() {
+        let parent = tempfile::tempdir().unwrap();
+        let out = parent.path().join("out");
+        std::fs::create_dir_all(&out).unwrap();
+        std::fs::write(out.join("delete"), "removed\n").unwrap();
+        std::fs::write(out.join("update"), "old\n").unwrap();
+
+        // `a/b` can not be staged below the file `a`
+        let err = apply(&out, &[
+            update("delete", Change::Delete, None),
+            update("update", Change::Update, Some("new\n")),
+            update("a", Change::Create, Some("a\n")),
+            update("a/b", Change::Create, Some("b\n")),
+        ]);
+        assert!(err.is_err());
+        assert_eq!(file_names(parent.path()), vec!["out", "out/delete", "out/update"]);
+        assert_eq!(std::fs::read_to_string(out.join("update")).unwrap(), "old\n");
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    collections::BTreeSet,
+    path::Path,
+};
+
+use anyhow::Result;
+
+use crate::{
+    blueprint::{
+        self,
+        Blueprint,
+        Validation,
+        VariableType,
+    },
+    render,
+};
+
+/// Checks a template folder without rendering it: the blueprint has to parse
+/// and declare a supported version, every file and path has to compile as a
+/// handlebars template and every referenced variable has to be declared.
+/// Prints all findings and fails if any of them is an error.
+pub fn validate(root_dir: &Path) -> Result<()> {
+    let bp = Blueprint::read(root_dir)?;
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (name, nested) in bp.overlapping_names() {
+        errors.push(format!(
+            "{}: variable \"{}\" overlaps with \"{}\", a variable can not have nested variables",
+            blueprint::FILE_NAME,
+            name,
+            nested
+        ));
+    }
+
+    if !blueprint::SUPPORTED_VERSIONS.contains(&bp.version.as_str()) {
+        errors.push(format!(
+            "{}: unsupported version \"{}\" (supported: {})",
+            blueprint::FILE_NAME,
+            bp.version,
+            blueprint::SUPPORTED_VERSIONS.join(", ")
+        ));
+    }
+
+    // (location, template source) of everything that is rendered
+    let mut sources = Vec::new();
+    for (name, variable) in bp.template.variables.iter().flatten() {
+        if let Some(when) = &variable.when {
+            sources.push((format!("{} (when of {})", blueprint::FILE_NAME, name), when.to_owned()));
+        }
+        if matches!(variable.kind, VariableType::Enum) && variable.values.as_ref().is_none_or(|v| v.is_empty()) {
+            errors.push(format!(
+                "{}: enum variable {} has no values",
+                blueprint::FILE_NAME,
+                name
+            ));
+        }
+        for validation in variable.validate.iter().flatten() {
+            if let Validation::Regex(regex) = validation {
+                if let Err(e) = regex::Regex::new(regex) {
+                    errors.push(format!("{}: invalid regex of {}: {}", blueprint::FILE_NAME, name, e));
+                }
+            }
+        }
+    }
+    for (pattern, expression) in bp.template.rules.iter().flatten() {
+        sources.push((
+            format!("{} (rule {})", blueprint::FILE_NAME, pattern),
+            expression.to_owned(),
+        ));
+    }
+    for (name, expression) in bp.template.computed.iter().flatten() {
+        sources.push((
+            format!("{} (computed {})", blueprint::FILE_NAME, name),
+            expression.to_owned(),
+        ));
+    }
+
+    let copy = render::glob_set(bp.template.copy.iter().flatten().map(|g| g.as_str()))?;
+    for entry in render::walk_template(root_dir, &bp)? {
+        let entry = entry?;
+        let template_path = entry.path().strip_prefix(root_dir)?;
+        if let Some(name) = entry.file_name().to_str() {
+            sources.push((format!("{} (path)", template_path.display()), name.to_owned()));
+        }
+        if entry.file_type().is_file() && !copy.is_match(template_path) {
+            let data = std::fs::read(entry.path())?;
+            if !render::is_binary(&data) {
+                sources.push((template_path.display().to_string(), String::from_utf8(data)?));
+            }
+        }
+    }
+
+    let reference = regex::Regex::new(render::VARIABLE_REFERENCE)?;
+    let declared = bp
+        .template
+        .variables
+        .iter()
+        .flat_map(|v| v.keys())
+        .chain(bp.template.computed.iter().flat_map(|c| c.keys()))
+        .collect::<BTreeSet<_>>();
+    let mut used = BTreeSet::new();
+    let mut undeclared = BTreeSet::new();
+    for (location, source) in &sources {
+        let template = match handlebars::Template::compile(source) {
+            | Ok(template) => template,
+            | Err(e) => {
+                match e.pos() {
+                    | Some((line, column)) => errors.push(format!("{}:{}:{}: {}", location, line, column, e.reason())),
+                    | None => errors.push(format!("{}: {}", location, e.reason())),
+                }
+                continue;
+            },
+        };
+        let mut references = Vec::new();
+        render::template_references(&template, &reference, &mut references);
+        for (path, (line, column)) in references {
+            // `vars.app.name` uses `app.name` or `app`, `vars.app` uses every `app.*`
+            let matches = declared
+                .iter()
+                .filter(|d| {
+                    path == ***d || path.starts_with(&format!("{}.", d)) || d.starts_with(&format!("{}.", path))
+                })
+                .collect::<Vec<_>>();
+            if matches.is_empty() && undeclared.insert((location, path.clone())) {
+                errors.push(format!(
+                    "{}:{}:{}: variable \"{}\" is used but not declared",
+                    location, line, column, path
+                ));
+            }
+            used.extend(matches);
+        }
+    }
+    for name in declared.difference(&used) {
+        warnings.push(format!(
+            "{}: variable \"{}\" is declared but never used",
+            blueprint::FILE_NAME,
+            name
+        ));
+    }
+
+    for error in &errors {
+        println!("error: {}", error);
+    }
+    for warning in &warnings {
+        println!("warning: {}", warning);
+    }
+    if !errors.is_empty() {
+        return Err(anyhow::anyhow!(
+            "validation failed with {} error(s) and {} warning(s)",
+            errors.len(),
+            warnings.len()
+        ));
+    }
+    println!("template is valid ({} warning(s))", warnings.len());
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+};
+
+use anyhow::Result;
+
+/// Loads the variables from a varfile. YAML, JSON and TOML files are detected
+/// by their extension and nested maps are flattened to dotted keys
+/// (`app: { name: x }` becomes `app.name=x`). Every other file is read as a
+/// dotenv file.
+pub fn load(path: &Path) -> Result<HashMap<String, String>> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read varfile {}: {}", path.display(), e))?;
+    let invalid = |e: &dyn std::fmt::Display| anyhow::anyhow!("invalid varfile {}: {}", path.display(), e);
+
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let value = match extension.as_str() {
+        | "yaml" | "yml" => serde_yaml::from_str::<serde_json::Value>(&data).map_err(|e| invalid(&e))?,
+        | "json" => serde_json::from_str::<serde_json::Value>(&data).map_err(|e| invalid(&e))?,
+        | "toml" => serde_json::to_value(toml::from_str::<toml::Table>(&data).map_err(|e| invalid(&e))?)?,
+        | _ => return dotenv(&data).map_err(|e| invalid(&e)),
+    };
+
+    let mut vars = HashMap::new();
+    match value {
+        | serde_json::Value::Object(_) => flatten("", &value, &mut vars),
+        | serde_json::Value::Null => {},
+        | _ => return Err(invalid(&"expected a map of variables")),
+    }
+    Ok(vars)
+}
+
+fn flatten(prefix: &str, value: &serde_json::Value, vars: &mut HashMap<String, String>) {
+    let scalar = |v: &serde_json::Value| {
+        match v {
+            | serde_json::Value::String(s) => Some(s.to_owned()),
+            | serde_json::Value::Null => Some(String::new()),
+            | serde_json::Value::Bool(_) | serde_json::Value::Number(_) => Some(v.to_string()),
+            | _ => None,
+        }
+    };
+    match value {
+        | serde_json::Value::Object(map) => {
+            for (k, v) in map {
+                let key = if prefix.is_empty() {
+                    k.to_owned()
+                } else {
+                    format!("{}.{}", prefix, k)
+                };
+                flatten(&key, v, vars);
+            }
+        },
+        // lists of scalars are passed as JSON list of strings (see `list<string>`)
+        | serde_json::Value::Array(items) => {
+            let value = match items.iter().map(scalar).collect::<Option<Vec<_>>>() {
+                | Some(items) => serde_json::Value::from(items).to_string(),
+                | None => value.to_string(),
+            };
+            vars.insert(prefix.to_owned(), value);
+        },
+        | _ => {
+            vars.insert(prefix.to_owned(), scalar(value).unwrap_or_default());
+        },
+    }
+}
+
+/// Parses dotenv syntax: `KEY=value` lines with an optional `export` prefix,
+/// `#` comments, single quoted (literal) and double quoted (escaped) values
+/// which may span multiple lines.
+fn dotenv(data: &str) -> Result<HashMap<String, String>> {
+    let mut vars = HashMap::new();
+    let mut lines = data.lines().enumerate().map(|(i, l)| (i + 1, l));
+    while let Some((number, line)) = lines.next() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(anyhow::anyhow!("line {}: expected KEY=VALUE", number));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.chars().any(|c| c.is_whitespace() || c == '#') {
+            return Err(anyhow::anyhow!("line {}: invalid key \"{}\"", number, key));
+        }
+
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            | Some('#') => String::new(),
+            | Some(quote @ ('"' | '\'')) => {
+                let mut raw = value[1..].to_owned();
+                let end = loop {
+                    if let Some(end) = closing_quote(&raw, quote) {
+                        break end;
+                    }
+                    let Some((_, next)) = lines.next() else {
+                        return Err(anyhow::anyhow!("line {}: unterminated quoted value", number));
+                    };
+                    raw.push('\n');
+                    raw.push_str(next);
+                };
+                let rest = raw[end + 1..].trim();
+                if !rest.is_empty() && !rest.starts_with('#') {
+                    return Err(anyhow::anyhow!(
+                        "line {}: unexpected \"{}\" after quoted value",
+                        number,
+                        rest
+                    ));
+                }
+                if quote == '"' {
+                    unescape(&raw[..end])
+                } else {
+                    raw[..end].to_owned()
+                }
+            },
+            | _ => {
+                // an unquoted `#` preceded by whitespace starts a comment
+                let value = match value.find(" #").or_else(|| value.find("\t#")) {
+                    | Some(i) => &value[..i],
+                    | None => value,
+                };
+                value.trim_end().to_owned()
+            },
+        };
+        vars.insert(key.to_owned(), value);
+    }
+    Ok(vars)
+}
+
+/// The byte index of the first unescaped `quote` in `raw`.
+fn closing_quote(raw: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in raw.char_indices() {
+        match c {
+            | '\\' if quote == '"' && !escaped => escaped = true,
+            | c if c == quote && !escaped => return Some(i),
+            | _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            | Some('n') => value.push('\n'),
+            | Some('r') => value.push('\r'),
+            | Some('t') => value.push('\t'),
+            | Some(c @ ('"' | '\\' | '$' | '\'')) => value.push(c),
+            | Some(c) => {
+                value.push('\\');
+                value.push(c);
+            },
+            | None => value.push('\\'),
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn load_str(name: &str, data: &str) -> Result<HashMap<String, String>> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, data).unwrap();
+        load(&path)
+    }
+
+    #[test]
+    fn dotenv_comments_and_export() {
+        let data = "# comment\n\n  export A=1\nB=two words # trailing\nC=#not a comment\nD=a#b\nE=\n";
+        assert_eq!(
+            dotenv(data).unwrap(),
+            vars(&[("A", "1"), ("B", "two words"), ("C", ""), ("D", "a#b"), ("E", "")])
+        );
+    }
+
+    #[test]
+    fn dotenv_quotes_and_escapes() {
+        let data = r#"A='single $x \n # kept'
+B="double \"quoted\" \n\t\\ \$x \q"
+C="x" # comment
+"#;
+        assert_eq!(
+            dotenv(data).unwrap(),
+            vars(&[
+                ("A", r"single $x \n # kept"),
+                ("B", "double \"quoted\" \n\t\\ $x \\q"),
+                ("C", "x")
+            ])
+        );
+        assert_eq!(closing_quote(r#"a\"b"c"#, '"'), Some(4));
+        assert_eq!(closing_quote(r"a\'b", '\''), Some(2));
+        assert_eq!(unescape(r#"\n\r\t\\\"\'\$\x\"#), "\n\r\t\\\"'$\\x\\");
+    }
+
+    #[test]
+    fn dotenv_multiline_values() {
+        let data = "A=\"first\nsecond\"\nB='x\n\ny'\nC=after\n";
+        assert_eq!(
+            dotenv(data).unwrap(),
+            vars(&[("A", "first\nsecond"), ("B", "x\n\ny"), ("C", "after")])
+        );
+    }
+
+    #[test]
+    fn dotenv_errors_report_the_line() {
+        let err = |data: &str| dotenv(data).unwrap_err().to_string();
+        assert_eq!(err("A=1\nB=\"open\nC=2\n"), "line 2: unterminated quoted value");
+        assert_eq!(err("A=1\n\nnot a pair\n"), "line 3: expected KEY=VALUE");
+        assert_eq!(err("A B=1"), "line 1: invalid key \"A B\"");
+        assert_eq!(err("A='x' y"), "line 1: unexpected \"y\" after quoted value");
+    }
+
+    #[test]
+    fn nested_maps_are_flattened() {
+        let expected = vars(&[
+            ("app.name", "demo"),
+            ("app.port", "8080"),
+            ("app.debug", "true"),
+            ("tags", r#"["a","b"]"#),
+            ("top", "x"),
+        ]);
+        let yaml = "app:\n  name: demo\n  port: 8080\n  debug: true\ntags: [a, b]\ntop: x\n";
+        assert_eq!(load_str("vars.yaml", yaml).unwrap(), expected);
+        let toml = "top = \"x\"\ntags = [\"a\", \"b\"]\n[app]\nname = \"demo\"\nport = 8080\ndebug = true\n";
+        assert_eq!(load_str("vars.toml", toml).unwrap(), expected);
+        let json = r#"{"app": {"name": "demo", "port": 8080, "debug": true}, "tags": ["a", "b"], "top": "x"}"#;
+        assert_eq!(load_str("vars.json", json).unwrap(), expected);
+
+        assert!(load_str("vars.yaml", "- a\n- b\n").is_err());
+        assert_eq!(load_str("vars.env", "A=1\n").unwrap(), vars(&[("A", "1")]));
+    }
+}